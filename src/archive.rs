@@ -0,0 +1,70 @@
+use chrono::{Datelike, Month};
+use minijinja::{context, Value};
+
+use crate::{blog_post::BlogPost, config::SiteConfig};
+
+/// The posts published in one year, newest month first.
+pub struct Year<'a> {
+    pub year: i32,
+    pub months: Vec<ArchiveMonth<'a>>,
+}
+
+/// The posts published in one month, newest first.
+pub struct ArchiveMonth<'a> {
+    /// Number of the month, from 1 for January.
+    pub month: u32,
+    pub posts: Vec<&'a BlogPost>,
+}
+
+impl Year<'_> {
+    /// The year as seen by templates, with its `months`, each with a `number`, a `name` and
+    /// its `posts`.
+    pub fn context(&self, config: &SiteConfig) -> Value {
+        context! {
+            year => self.year,
+            count => self.months.iter().map(|month| month.posts.len()).sum::<usize>(),
+            months => self
+                .months
+                .iter()
+                .map(|month| context! {
+                    number => month.month,
+                    name => month_name(month.month),
+                    posts => month.posts.iter().map(|post| post.context(config)).collect::<Vec<_>>(),
+                })
+                .collect::<Vec<_>>(),
+        }
+    }
+}
+
+/// Groups posts sorted newest first by the year and month of their `published` date.
+pub fn collect_archive(blog_posts: &[BlogPost]) -> Vec<Year<'_>> {
+    let mut years: Vec<Year> = Vec::new();
+    for post in blog_posts {
+        let published = post.published();
+        if years
+            .last()
+            .is_none_or(|year| year.year != published.year())
+        {
+            years.push(Year {
+                year: published.year(),
+                months: Vec::new(),
+            });
+        }
+        let months = &mut years.last_mut().expect("a year was just pushed").months;
+        match months.last_mut() {
+            Some(month) if month.month == published.month() => month.posts.push(post),
+            _ => months.push(ArchiveMonth {
+                month: published.month(),
+                posts: vec![post],
+            }),
+        }
+    }
+    years
+}
+
+fn month_name(month: u32) -> &'static str {
+    u8::try_from(month)
+        .ok()
+        .and_then(|month| Month::try_from(month).ok())
+        .map_or("", |month| month.name())
+}
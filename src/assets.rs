@@ -0,0 +1,89 @@
+//! Copies the files next to a post's or page's content into the generated site.
+
+use std::{fs, path::Path};
+
+use crate::{
+    cache::copy_if_changed,
+    config::SiteConfig,
+    error::{IoResultExt, Result, ScrigenError},
+};
+
+/// Copies everything in `source_directory` except `content.md`, `meta.ron` and nested pages
+/// into `target_directory`, including subdirectories, and skips files matching the site's
+/// ignore list. Copying carries on past failures, which are returned together.
+pub fn copy_assets(
+    config: &SiteConfig,
+    source_directory: &str,
+    target_directory: &str,
+) -> Result<()> {
+    let mut errors = Vec::new();
+    copy_directory(config, source_directory, target_directory, "", &mut errors);
+    if !errors.is_empty() {
+        return Err(ScrigenError::Assets(errors));
+    }
+    Ok(())
+}
+
+/// Copies the directory at `relative` inside `source_root` to the same place in `target_root`.
+fn copy_directory(
+    config: &SiteConfig,
+    source_root: &str,
+    target_root: &str,
+    relative: &str,
+    errors: &mut Vec<ScrigenError>,
+) {
+    let source_directory = match relative {
+        "" => source_root.to_string(),
+        relative => format!("{source_root}/{relative}"),
+    };
+    let entries = match fs::read_dir(&source_directory).with_path(&source_directory) {
+        Ok(entries) => entries,
+        Err(error) => {
+            errors.push(error);
+            return;
+        }
+    };
+    for entry in entries {
+        let entry = match entry.with_path(&source_directory) {
+            Ok(entry) => entry,
+            Err(error) => {
+                errors.push(error);
+                continue;
+            }
+        };
+        let name = entry.file_name().to_string_lossy().to_string();
+        if relative.is_empty() && (name == "content.md" || name == "meta.ron") {
+            continue;
+        }
+        let relative_path = match relative {
+            "" => name,
+            relative => format!("{relative}/{name}"),
+        };
+        if config.is_ignored(&relative_path) {
+            continue;
+        }
+        let source_path = format!("{source_root}/{relative_path}");
+        let target_path = format!("{target_root}/{relative_path}");
+        match entry.file_type().with_path(&source_path) {
+            // Pages nested in a page are written on their own.
+            Ok(file_type)
+                if file_type.is_dir()
+                    && relative.is_empty()
+                    && Path::new(&source_path).join("meta.ron").is_file() => {}
+            Ok(file_type) if file_type.is_dir() => {
+                match fs::create_dir_all(&target_path).with_path(&target_path) {
+                    Ok(()) => {
+                        copy_directory(config, source_root, target_root, &relative_path, errors)
+                    }
+                    Err(error) => errors.push(error),
+                }
+            }
+            Ok(_) => {
+                if let Err(error) = copy_if_changed(&source_path, &target_path) {
+                    errors.push(error);
+                }
+            }
+            Err(error) => errors.push(error),
+        }
+    }
+}
@@ -1,12 +1,19 @@
-use atom_syndication::{Content, FixedDateTime, Link, Person};
-use chrono::{Local, NaiveDate, NaiveDateTime, NaiveTime};
-use convert_case::Casing;
-use inkjet::{formatter, Highlighter};
-use regex::{Captures, Regex};
+use atom_syndication::{Category, Content, FixedDateTime, Link, Person};
+use chrono_tz::Tz;
+use minijinja::{context, Value};
 use serde::Deserialize;
 use std::fs;
 
-use crate::{page::Page, static_page::StaticPage};
+use crate::{
+    config::SiteConfig,
+    date::{commit_date, file_date, Commit, DateSource, Timestamp},
+    error::{IoResultExt, Result, ScrigenError},
+    page::{read_ron, Page},
+    site::root_of,
+    tags::{slug, tag_context},
+    templates::sections_value,
+    Site,
+};
 
 #[derive(Deserialize, Default)]
 pub struct BlogPost {
@@ -14,75 +21,153 @@ pub struct BlogPost {
     pub id: String,
     pub title: String,
     pub summary: String,
-    author_name: String,
-    author_email: String,
-    author_uri: String,
-    author_fediverse: String,
+    #[serde(default)]
+    author_name: Option<String>,
+    #[serde(default)]
+    author_email: Option<String>,
+    #[serde(default)]
+    author_uri: Option<String>,
+    #[serde(default)]
+    author_fediverse: Option<String>,
     pub image: String,
     pub image_alt: String,
-    pub published: String,
-    pub updated: String,
+    /// Falls back to the first commit touching the post, see [`BlogPost::published_source`].
+    #[serde(default)]
+    pub published: Option<String>,
+    /// Falls back to the last commit touching the post, see [`BlogPost::updated_source`].
+    #[serde(default)]
+    pub updated: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Name of the series the post is part of, whose posts link to each other.
+    #[serde(default)]
+    pub series: Option<String>,
+    /// Drafts are left out of the build unless it includes drafts explicitly.
+    #[serde(default)]
+    pub draft: bool,
     #[serde(default = "String::new")]
     pub markdown: String,
+    #[serde(skip)]
+    published_date: FixedDateTime,
+    #[serde(skip)]
+    updated_date: FixedDateTime,
+    #[serde(skip)]
+    published_source: DateSource,
+    #[serde(skip)]
+    updated_source: DateSource,
+    /// Directory the post was read from.
+    #[serde(skip)]
+    pub source_path: String,
+}
+
+fn parse_date(path: &str, field: &'static str, value: &str, timezone: Tz) -> Result<FixedDateTime> {
+    let timestamp: Timestamp = value.parse().map_err(|source| ScrigenError::Date {
+        path: path.to_string(),
+        field,
+        value: value.to_string(),
+        source,
+    })?;
+    Ok(timestamp.in_timezone(timezone))
+}
+
+/// Takes a date from `meta.ron` if it is set there, otherwise from the git history of the post's
+/// directory and finally from the creation or modification time of its files.
+fn resolve_date(
+    path: &str,
+    field: &'static str,
+    value: Option<&str>,
+    commit: Commit,
+    timezone: Tz,
+) -> Result<(FixedDateTime, DateSource)> {
+    if let Some(value) = value {
+        let date = parse_date(&format!("{path}/meta.ron"), field, value, timezone)?;
+        return Ok((date, DateSource::Meta));
+    }
+    if let Some(date) = commit_date(path, commit, timezone) {
+        return Ok((date, DateSource::Git));
+    }
+    Ok((file_date(path, commit, timezone)?, DateSource::Files))
 }
 
 impl BlogPost {
     pub fn published(&self) -> FixedDateTime {
-        FixedDateTime::from_naive_utc_and_offset(
-            NaiveDateTime::new(
-                NaiveDate::parse_from_str(&self.published, "%Y-%m-%d").unwrap(),
-                NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
-            ),
-            Local::now().offset().clone(),
-        )
+        self.published_date
     }
 
     pub fn updated(&self) -> FixedDateTime {
-        FixedDateTime::from_naive_utc_and_offset(
-            NaiveDateTime::new(
-                NaiveDate::parse_from_str(&self.updated, "%Y-%m-%d").unwrap(),
-                NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+        self.updated_date
+    }
+
+    /// Where the published date was taken from.
+    pub fn published_source(&self) -> DateSource {
+        self.published_source
+    }
+
+    /// Where the updated date was taken from.
+    pub fn updated_source(&self) -> DateSource {
+        self.updated_source
+    }
+
+    /// The post's author, falling back to the site's default author.
+    pub fn author(&self, config: &SiteConfig) -> Person {
+        Person {
+            name: self
+                .author_name
+                .clone()
+                .unwrap_or_else(|| config.author.name.clone()),
+            email: Some(
+                self.author_email
+                    .clone()
+                    .unwrap_or_else(|| config.author.email.clone()),
             ),
-            Local::now().offset().clone(),
-        )
+            uri: Some(
+                self.author_uri
+                    .clone()
+                    .unwrap_or_else(|| config.author.uri.clone()),
+            ),
+        }
     }
 
-    pub fn to_entry_fragment(&self) -> String {
-        format!(
-            "<div class='post-entry'>
-                <img class='entry-image' src='blog/{}/{}' alt='{}'/>
-                <div class='entry-text'>
-                    <a href='blog/{}/index.html' class='entry-link'></a>
-         			<h2 class='entry-title'>{}</h2>
-         			<span class='entry-date'>{}</span>
-         			<p class='entry-summary'>{}</p>
-                </div>
-			</div>",
-            self.id,
-            self.image,
-            self.image_alt,
-            self.id,
-            self.title,
-            self.published().format("%B %e, %Y"),
-            self.summary
-        )
+    pub fn author_fediverse<'a>(&'a self, config: &'a SiteConfig) -> &'a str {
+        self.author_fediverse
+            .as_deref()
+            .unwrap_or(&config.author.fediverse)
+    }
+
+    pub fn url(&self, config: &SiteConfig) -> String {
+        format!("{}/{}/{}", config.base_url, config.output.blog, self.id)
     }
 
-    pub fn get_atom_entry(
-        &self,
-        source_dir: &String,
-        pages: &Vec<StaticPage>,
-        url_base: &String,
-        site_name: &String,
-    ) -> atom_syndication::Entry {
+    /// The post as seen by templates.
+    pub fn context(&self, config: &SiteConfig) -> Value {
+        let author = self.author(config);
+        context! {
+            id => self.id,
+            url => self.url(config),
+            title => self.title,
+            summary => self.summary,
+            image => self.image,
+            image_alt => self.image_alt,
+            published => self.published().to_rfc3339(),
+            updated => self.updated().to_rfc3339(),
+            draft => self.draft,
+            tags => self.tags.iter().map(|tag| tag_context(tag)).collect::<Vec<_>>(),
+            series => self.series,
+            author => context! {
+                name => author.name,
+                email => author.email,
+                uri => author.uri,
+                fediverse => self.author_fediverse(config),
+            },
+        }
+    }
+
+    pub fn get_atom_entry(&self, site: &Site) -> Result<atom_syndication::Entry> {
+        let config = site.config();
         let mut entry = atom_syndication::Entry::default();
         entry.set_title(self.title.clone());
-        entry.set_authors(vec![Person {
-            name: self.author_name.clone(),
-            email: Some(self.author_email.clone()),
-            uri: Some(self.author_uri.clone()),
-        }]);
-        let post_url = format!("{url_base}/blog/{}", self.id);
+        entry.set_authors(vec![self.author(config)]);
+        let post_url = self.url(config);
         entry.set_id(&post_url);
         entry.set_links(vec![Link {
             href: post_url.clone(),
@@ -93,219 +178,92 @@ impl BlogPost {
         entry.set_summary(Some(atom_syndication::Text::plain(self.summary.clone())));
         entry.set_published(self.published());
         entry.set_updated(self.updated());
+        entry.set_categories(
+            self.tags
+                .iter()
+                .map(|tag| Category {
+                    term: tag.clone(),
+                    ..Default::default()
+                })
+                .collect::<Vec<_>>(),
+        );
         let content = Content {
             base: Some(post_url.clone()),
-            lang: Some("en".into()),
-            value: Some(self.to_html(source_dir, pages, url_base, site_name)),
+            lang: Some(config.language.clone()),
+            value: Some(site.render_post(self)?),
             src: Some(post_url.clone()),
             content_type: Some("html".into()),
         };
         entry.set_content(content);
-        entry
+        Ok(entry)
     }
 }
 
 impl Page for BlogPost {
-    fn from_path(path: String) -> Self {
-        let meta_string = fs::read_to_string(path.clone() + "/meta.ron").unwrap();
-        let mut post: Self = ron::from_str(meta_string.as_str()).unwrap();
-        post.markdown = fs::read_to_string(path + "/content.md").unwrap();
-        post
-    }
-
-    fn to_html(
-        &self,
-        source_dir: &String,
-        pages: &Vec<StaticPage>,
-        url_base: &String,
-        site_name: &String,
-    ) -> String {
-        let mut html = format!(
-            "<!DOCTYPE html>
-        <html>
-        <head>
-	<meta http-equiv=\"Content-Type\" content=\"text/html; charset=utf-8\">
-	<meta property=\"og:site_name\" content=\"{site_name}\">
-	<meta property=\"og:type\" content=\"website\" />
-	<meta property=\"og:url\" content=\"{url_base}/blog/{}\">
-	<meta property=\"og:title\" content=\"{}\" />
-	<meta property=\"og:description\" content=\"{}\">
-	<meta property=\"og:image\" content=\"{url_base}/blog/{}/{}\"/>
-    <meta name='fediverse:creator' content='{}'/>
-	<title>{}</title>",
-            self.id,
-            self.title,
-            self.summary,
-            self.id,
-            self.image,
-            self.author_fediverse,
-            self.title
-        );
-        let mut highlighter = Highlighter::new();
-
-        let header =
-            fs::read_to_string(format!("{source_dir}/fragments/post_header.html")).unwrap();
-
-        let mut page_links = String::from("<a href=\"../../index.html\">Blog</a>");
-        for page in pages {
-            page_links +=
-                format!("<a href=\"../../{}/index.html\">{}</a>", page.id, page.name).as_str();
+    fn from_path(path: String, config: &SiteConfig) -> Result<Self> {
+        let meta_path = path.clone() + "/meta.ron";
+        let mut post: Self = read_ron(&meta_path)?;
+        post.source_path = path.clone();
+        let mut errors: Vec<ScrigenError> = Vec::new();
+        let published = post.published.as_deref();
+        match resolve_date(
+            &path,
+            "published",
+            published,
+            Commit::First,
+            config.timezone,
+        ) {
+            Ok((date, source)) => (post.published_date, post.published_source) = (date, source),
+            Err(error) => errors.push(error),
         }
-
-        let header = header.replace("___STATIC_PAGES___", &page_links);
-        html += header.as_str();
-
-        html += format!(
-            "<div class='post-header-image' id='page-top'>
-                <img alt='{}' src='{}' class='post-image'><br />
-            </div>
-            ___SIDEBAR___
-            ",
-            self.image_alt, self.image
-        )
-        .as_str();
-
-        html += "<div class='post-text'>";
-        html += format!("<h1>{}</h1>", self.title).as_str();
-
-        let mut in_code_block = false;
-        let mut current_code_block = String::new();
-        let mut format_gdscript = false;
-        let mut in_image = false;
-
-        let mut sections: Vec<(String, String)> = vec![];
-
-        for line in self.markdown.lines() {
-            if line.starts_with("```") {
-                in_code_block = !in_code_block;
-                if in_code_block {
-                    format_gdscript = line.starts_with("```GDScript");
-                } else {
-                    if format_gdscript {
-                        let result = highlighter
-                            .highlight_to_string(
-                                inkjet::Language::Gdscript,
-                                &formatter::Html,
-                                current_code_block.as_str(),
-                            )
-                            .unwrap();
-                        // Doing crimes against Regex
-                        let mut inside_tag = false;
-                        let mut new_result = String::new();
-                        for character in result.chars() {
-                            match character {
-                                '<' => inside_tag = true,
-                                '>' => inside_tag = false,
-                                '=' => {
-                                    if !inside_tag {
-                                        new_result += "&equals;";
-                                        continue;
-                                    }
-                                }
-                                _ => {}
-                            };
-                            new_result.push(character);
-                        }
-                        let regex = Regex::new(
-                            "(\\(|\\)|\\[|\\]|\\:|\\+|\\-)|\\*|\\{|\\}|&gt;|&#x2f;|&equals;",
-                        )
-                        .unwrap();
-                        let result =
-                            regex.replace_all(new_result.as_str(), |captures: &Captures| {
-                                format!("<span class='symbol'>{}</span>", &captures[0])
-                            });
-                        html += format!("<pre>{}</pre>", result).as_str();
-                    } else {
-                        html += format!("<pre>{}</pre>", current_code_block.as_str()).as_str();
-                    }
-                    current_code_block.clear();
-                }
-                continue;
-            }
-            if in_code_block {
-                current_code_block += format!("{line}\n").as_str();
-                continue;
+        let updated = post.updated.as_deref();
+        match resolve_date(&path, "updated", updated, Commit::Last, config.timezone) {
+            Ok((date, source)) => (post.updated_date, post.updated_source) = (date, source),
+            Err(error) => errors.push(error),
+        }
+        for tag in &post.tags {
+            if slug(tag).is_empty() {
+                errors.push(ScrigenError::EmptyTagSlug {
+                    path: meta_path.clone(),
+                    tag: tag.clone(),
+                });
             }
-            if line.starts_with("!insert ") {
-                let markdown_part;
-                if line.starts_with("!insert bg ") {
-                    html += "</div><div class='post-insert with-background'><div class='insert-content'><div class='insert-content-inner'>";
+        }
+        let content_path = path + "/content.md";
+        match fs::read_to_string(&content_path).with_path(&content_path) {
+            Ok(markdown) => post.markdown = markdown,
+            Err(error) => errors.push(error),
+        }
+        if !errors.is_empty() {
+            return Err(ScrigenError::Content(errors));
+        }
+        Ok(post)
+    }
 
-                    markdown_part = line.replace("!insert bg ", "");
-                } else {
-                    html += "</div><div class='post-insert'><div class='insert-content'><div class='insert-content-inner'>";
-                    markdown_part = line.replace("!insert ", "");
-                }
+    fn markdown(&self) -> &str {
+        &self.markdown
+    }
 
-                html += markdown::to_html(markdown_part.as_str())
-                    .replace("<p>", "")
-                    .replace("</p>", "")
-                    .as_str();
-                in_image = true;
-                continue;
-            }
-            if in_image {
-                in_image = false;
-                if line.starts_with("!image_subtitle ") {
-                    html += format!(
-                        "<br><div class='insert-description'><em>{}</em>",
-                        line.replace("!image_subtitle ", "")
-                    )
-                    .as_str();
-                    html += "</div></div></div></div><div class='post-text'>\n";
-                    continue;
-                } else {
-                    html += "</div></div></div><div class='post-text'>\n";
-                }
-            }
-            let parsed_line =
-                markdown::to_html_with_options(line, &markdown::Options::gfm()).unwrap();
-            if parsed_line.starts_with("<h2>") {
-                let section_title = parsed_line[4..parsed_line.len() - 5].to_string();
-                let section_id: String = section_title
-                    .chars()
-                    .filter(|&c| c.is_alphanumeric() || c == ' ')
-                    .collect();
-                let section_id = section_id
-                    .replace(":", "")
-                    .to_case(convert_case::Case::Snake);
-                html += format!(
-                    "
-                <h2 id='{}'>{}<a href='#{}'><div class='section-link' alt='Section link'>
-                </div></a></h2>",
-                    section_id, section_title, section_id
-                )
-                .as_str();
-                sections.push((section_title, section_id));
-            } else {
-                html += parsed_line.as_str();
-            }
-        }
-        html += "<div class='post-end'>
-	<a href='../../index.html'><div id='home-link'></div>Home</a>
-	<a href='#page-top'><div id='top-link'></div>Back to the top</a>
-</div></div>";
-        let footer =
-            fs::read_to_string(format!("{source_dir}/fragments/post_footer.html")).unwrap();
-        html += footer.as_str();
+    fn source_path(&self) -> &str {
+        &self.source_path
+    }
 
-        let mut sidebar = format!(
-            "
-        <div id='sidebar'>
-        <div class='sidebar-info'>
-        Published <span class='sidebar-date'>{}</span><br>
-        Updated <span class='sidebar-date'>{}</span><br>
-        </div>
-        <hr>
-        <ol>",
-            self.published().format("%B %e, %Y"),
-            self.updated().format("%B %e, %Y")
-        );
-        for section in sections {
-            sidebar += format!("<li><a href='#{}'>{}</a></li>", section.1, section.0).as_str();
-        }
-        sidebar += "</ol></div>";
-        let html = html.replace("___SIDEBAR___", sidebar.as_str());
-        html
+    fn to_html(&self, site: &Site) -> Result<String> {
+        let config = site.config();
+        let content = self.render_content()?;
+        let directory = format!("{}/{}/", config.output.blog, self.id);
+        site.templates().render(
+            "post.html",
+            context! {
+                site => config,
+                post => self.context(config),
+                content => Value::from_safe_string(content.html),
+                sections => sections_value(&content.sections),
+                pages => site.page_tree(None),
+                menu => site.menu(&directory),
+                navigation => site.neighbours(self).context(config),
+                root => root_of(&directory),
+            },
+        )
     }
 }
@@ -0,0 +1,282 @@
+//! Build cache kept as `.scrigen-cache` in the target directory. It remembers a hash of the
+//! inputs of every output, so outputs whose inputs didn't change are neither rendered nor
+//! written again.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+};
+
+use crate::{
+    blog_post::BlogPost,
+    error::{IoResultExt, Result, ScrigenError},
+    page::read_ron,
+    static_page::StaticPage,
+    templates::DEFAULT_TEMPLATES,
+    Site,
+};
+
+const CACHE_FILE: &str = ".scrigen-cache";
+
+/// Input hashes of the outputs of a target directory, by output path relative to it.
+pub struct Cache {
+    target_directory: String,
+    previous: BTreeMap<String, u64>,
+    current: BTreeMap<String, u64>,
+    /// Hash of the inputs every output depends on: the configuration, templates, fragments
+    /// and the navigation, which also makes up the breadcrumbs and child lists of pages.
+    site_hash: u64,
+    /// Hash of every post that is part of the build, in order.
+    posts_hash: u64,
+    /// Hash of each post by id, so its files are only read once per build.
+    post_hashes: HashMap<String, u64>,
+}
+
+impl Cache {
+    /// Reads the cache of a target directory, a missing or unreadable cache is empty.
+    pub fn load(site: &Site, target_directory: &str) -> Result<Self> {
+        let config = site.config();
+        let mut hasher = DefaultHasher::new();
+        // Different versions of scrigen render differently and may hash differently, the
+        // default templates can change without a new version during development.
+        env!("CARGO_PKG_VERSION").hash(&mut hasher);
+        DEFAULT_TEMPLATES.hash(&mut hasher);
+        hash_path(
+            &format!("{}/site.ron", config.source_directory),
+            &mut hasher,
+        )?;
+        hash_path(&config.templates_directory(), &mut hasher)?;
+        hash_path(&config.fragments_directory(), &mut hasher)?;
+        for page in site.pages() {
+            (&page.id, page.order).hash(&mut hasher);
+            hash_path(&format!("{}/meta.ron", page.source_path), &mut hasher)?;
+        }
+        // Menu items can be labelled with the spelling of a tag, which comes from the posts.
+        site.menu("").hash(&mut hasher);
+        let site_hash = hasher.finish();
+
+        let mut hasher = DefaultHasher::new();
+        let mut post_hashes = HashMap::new();
+        for post in site.posts() {
+            let hash = post_hash(post)?;
+            hash.hash(&mut hasher);
+            post_hashes.insert(post.id.clone(), hash);
+        }
+        let posts_hash = hasher.finish();
+
+        let previous = read_ron(&format!("{target_directory}/{CACHE_FILE}")).unwrap_or_default();
+        Ok(Self {
+            target_directory: target_directory.to_string(),
+            previous,
+            current: BTreeMap::new(),
+            site_hash,
+            posts_hash,
+            post_hashes,
+        })
+    }
+
+    /// Key of a post's own page, which also shows the posts it links to at its end.
+    pub fn post_key(&self, site: &Site, post: &BlogPost) -> Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        match self.post_hashes.get(&post.id) {
+            Some(hash) => hash.hash(&mut hasher),
+            None => post_hash(post)?.hash(&mut hasher),
+        }
+        site.neighbours(post)
+            .context(site.config())
+            .hash(&mut hasher);
+        Ok(self.key(hasher.finish()))
+    }
+
+    /// Key of a static page.
+    pub fn page_key(&self, page: &StaticPage) -> Result<u64> {
+        let mut hasher = DefaultHasher::new();
+        hash_path(&page.source_path, &mut hasher)?;
+        Ok(self.key(hasher.finish()))
+    }
+
+    /// Key of outputs listing posts, like the landing page, tags and feeds.
+    pub fn posts_key(&self) -> u64 {
+        self.key(self.posts_hash)
+    }
+
+    fn key(&self, hash: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        (self.site_hash, hash).hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Records the key of an output and tells whether it is still up to date from the
+    /// previous build.
+    pub fn is_fresh(&mut self, output: &str, key: u64) -> bool {
+        self.current.insert(output.to_string(), key);
+        self.previous.get(output) == Some(&key)
+            && Path::new(&format!("{}/{output}", self.target_directory)).exists()
+    }
+
+    /// Deletes the outputs of the previous builds that this build didn't produce, like posts
+    /// that were deleted or turned back into drafts, tags without posts and years without
+    /// posts. Outputs named `index.html` take their directory with them unless it still
+    /// holds outputs of this build. Only meaningful after a full build.
+    pub fn remove_stale(&mut self) -> Result<()> {
+        for output in self.previous.keys() {
+            if self.current.contains_key(output) {
+                continue;
+            }
+            let path = format!("{}/{output}", self.target_directory);
+            let result = match output.strip_suffix("index.html") {
+                Some(directory)
+                    if !directory.is_empty()
+                        && !self.current.keys().any(|kept| kept.starts_with(directory)) =>
+                {
+                    fs::remove_dir_all(format!("{}/{directory}", self.target_directory))
+                }
+                _ => fs::remove_file(&path),
+            };
+            match result {
+                Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(error).with_path(&path)
+                }
+                _ => {}
+            }
+        }
+        let current = &self.current;
+        self.previous
+            .retain(|output, _| current.contains_key(output));
+        Ok(())
+    }
+
+    /// Writes the cache, keeping the keys of outputs that weren't part of this build.
+    pub fn save(mut self) -> Result<()> {
+        self.previous.append(&mut self.current);
+        let cache = ron::ser::to_string_pretty(&self.previous, Default::default())
+            .expect("maps of strings and numbers can always be serialized");
+        write_if_changed(
+            &format!("{}/{CACHE_FILE}", self.target_directory),
+            cache.as_bytes(),
+        )
+    }
+}
+
+fn post_hash(post: &BlogPost) -> Result<u64> {
+    let mut hasher = DefaultHasher::new();
+    post.id.hash(&mut hasher);
+    // Dates may come from the git history rather than the post's files.
+    (post.published(), post.updated()).hash(&mut hasher);
+    hash_path(&post.source_path, &mut hasher)?;
+    Ok(hasher.finish())
+}
+
+/// Hashes the names and contents of a file or of everything in a directory. Missing paths
+/// hash like empty ones.
+fn hash_path(path: &str, hasher: &mut DefaultHasher) -> Result<()> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(error) => return Err(error).with_path(path),
+    };
+    if metadata.is_dir() {
+        let mut names: Vec<String> = Vec::new();
+        for entry in fs::read_dir(path).with_path(path)? {
+            names.push(
+                entry
+                    .with_path(path)?
+                    .file_name()
+                    .to_string_lossy()
+                    .to_string(),
+            );
+        }
+        names.sort();
+        for name in names {
+            name.hash(hasher);
+            hash_path(&format!("{path}/{name}"), hasher)?;
+        }
+    } else {
+        fs::read(path).with_path(path)?.hash(hasher);
+    }
+    Ok(())
+}
+
+/// Writes a file unless it already has exactly these contents, so unchanged outputs keep
+/// their modification time.
+pub fn write_if_changed(path: &str, contents: &[u8]) -> Result<()> {
+    if fs::read(path).is_ok_and(|existing| existing == contents) {
+        return Ok(());
+    }
+    fs::write(path, contents).with_path(path)
+}
+
+/// Copies a file unless the target already has the same contents.
+pub fn copy_if_changed(source_path: &str, target_path: &str) -> Result<()> {
+    let source_length = fs::metadata(source_path).with_path(source_path)?.len();
+    if fs::metadata(target_path).is_ok_and(|target| target.len() == source_length)
+        && fs::read(source_path).with_path(source_path)?
+            == fs::read(target_path).unwrap_or_default()
+    {
+        return Ok(());
+    }
+    fs::copy(source_path, target_path).map_err(|source| ScrigenError::Copy {
+        source_path: source_path.to_string(),
+        target_path: target_path.to_string(),
+        source,
+    })?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_stale_deletes_outputs_missing_from_the_build() {
+        let target = std::env::temp_dir().join(format!("scrigen-cache-{}", std::process::id()));
+        let target_directory = target.to_string_lossy().to_string();
+        for directory in ["blog/draft", "blog/post", "about/team"] {
+            fs::create_dir_all(target.join(directory)).unwrap();
+        }
+        for file in [
+            "blog/draft/index.html",
+            "blog/draft/image.png",
+            "blog/post/index.html",
+            "about/index.html",
+            "about/team/index.html",
+            "blog/atom.xml",
+        ] {
+            fs::write(target.join(file), "").unwrap();
+        }
+        let mut cache = Cache {
+            target_directory,
+            previous: BTreeMap::new(),
+            current: BTreeMap::new(),
+            site_hash: 0,
+            posts_hash: 0,
+            post_hashes: HashMap::new(),
+        };
+        for output in [
+            "blog/draft/index.html",
+            "blog/post/index.html",
+            "about/index.html",
+            "about/team/index.html",
+            "blog/atom.xml",
+        ] {
+            cache.previous.insert(output.to_string(), 0);
+        }
+        cache.is_fresh("blog/post/index.html", 0);
+        cache.is_fresh("about/team/index.html", 0);
+
+        cache.remove_stale().unwrap();
+
+        assert!(!target.join("blog/draft").exists());
+        assert!(!target.join("blog/atom.xml").exists());
+        assert!(target.join("blog/post/index.html").exists());
+        assert!(!target.join("about/index.html").exists());
+        assert!(target.join("about/team/index.html").exists());
+        assert_eq!(
+            cache.previous.keys().collect::<Vec<_>>(),
+            ["about/team/index.html", "blog/post/index.html"]
+        );
+        fs::remove_dir_all(&target).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+use std::num::NonZeroUsize;
+
+use chrono_tz::Tz;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Result, ScrigenError},
+    menu::Menu,
+    page::read_ron,
+};
+
+/// Site-wide configuration, read once from `site.ron` in the source directory.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SiteConfig {
+    pub title: String,
+    /// Absolute URL of the deployed site, without a trailing slash.
+    pub base_url: String,
+    /// Atom feed id, defaults to the URL of the blog directory.
+    #[serde(default)]
+    pub feed_id: Option<String>,
+    /// Author used for posts and pages that don't name their own.
+    pub author: Author,
+    #[serde(default = "default_language")]
+    pub language: String,
+    /// IANA timezone of dates without an explicit offset, e.g. "Europe/Berlin".
+    #[serde(default = "default_timezone")]
+    pub timezone: Tz,
+    #[serde(default)]
+    pub directories: Directories,
+    #[serde(default)]
+    pub output: Output,
+    #[serde(default)]
+    pub features: Features,
+    /// Glob patterns of files in post and page directories that aren't copied, e.g. "*.psd".
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    /// Number of posts on each page of the landing page, all posts are on one page by default.
+    #[serde(default)]
+    pub posts_per_page: Option<NonZeroUsize>,
+    #[serde(default)]
+    pub post_navigation: PostNavigation,
+    /// Navigation menu, defaults to the blog followed by the pages.
+    #[serde(default)]
+    pub menu: Option<Menu>,
+    #[serde(skip)]
+    ignore_set: GlobSet,
+    #[serde(skip)]
+    pub source_directory: String,
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Author {
+    pub name: String,
+    pub email: String,
+    pub uri: String,
+    #[serde(default)]
+    pub fediverse: String,
+}
+
+/// Names of the content directories inside the source directory.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Directories {
+    pub entries: String,
+    pub pages: String,
+    pub fragments: String,
+    pub templates: String,
+}
+
+impl Default for Directories {
+    fn default() -> Self {
+        Self {
+            entries: "entries".into(),
+            pages: "pages".into(),
+            fragments: "fragments".into(),
+            templates: "templates".into(),
+        }
+    }
+}
+
+/// Layout of the generated site inside the target directory.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Output {
+    /// Directory blog posts are written to.
+    pub blog: String,
+    /// File name of the Atom feed inside the blog directory.
+    pub feed: String,
+    /// Directory tag pages are written to, inside the blog directory.
+    pub tags: String,
+    /// Directory the archive of all posts is written to, inside the blog directory. Each year
+    /// also gets its own page, e.g. `blog/2024/`.
+    pub archive: String,
+    /// Directory the pages of the landing page after the first are written to, as
+    /// `<pagination>/2/index.html` and so on.
+    pub pagination: String,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Self {
+            blog: "blog".into(),
+            feed: "atom.xml".into(),
+            tags: "tags".into(),
+            archive: "archive".into(),
+            pagination: "page".into(),
+        }
+    }
+}
+
+/// Links at the end of each post to other posts, besides the previous and next post.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct PostNavigation {
+    /// Link to the previous and next post of the same series.
+    pub series: bool,
+    /// Tags whose posts link to the previous and next post with the same tag.
+    pub tags: Vec<String>,
+}
+
+impl Default for PostNavigation {
+    fn default() -> Self {
+        Self {
+            series: true,
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// Build steps that can be turned off entirely.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(default)]
+pub struct Features {
+    pub landing: bool,
+    pub pages: bool,
+    pub feed: bool,
+    pub tags: bool,
+    pub archive: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            landing: true,
+            pages: true,
+            feed: true,
+            tags: true,
+            archive: true,
+        }
+    }
+}
+
+fn default_language() -> String {
+    "en".into()
+}
+
+fn default_timezone() -> Tz {
+    Tz::UTC
+}
+
+impl SiteConfig {
+    pub fn load(source_directory: &str) -> Result<Self> {
+        let path = format!("{source_directory}/site.ron");
+        let mut config: Self = read_ron(&path)?;
+        config.source_directory = source_directory.to_string();
+        let mut ignore_set = GlobSetBuilder::new();
+        for pattern in &config.ignore {
+            let glob = Glob::new(pattern).map_err(|source| ScrigenError::IgnorePattern {
+                path: path.clone(),
+                pattern: pattern.clone(),
+                source,
+            })?;
+            ignore_set.add(glob);
+        }
+        config.ignore_set = ignore_set
+            .build()
+            .map_err(|source| ScrigenError::IgnorePattern {
+                path: path.clone(),
+                pattern: config.ignore.join(", "),
+                source,
+            })?;
+        Ok(config)
+    }
+
+    /// Whether a file inside a post or page directory matches the ignore list, either by its
+    /// path relative to that directory or by its name alone.
+    pub fn is_ignored(&self, relative_path: &str) -> bool {
+        let name = relative_path.rsplit('/').next().unwrap_or(relative_path);
+        self.ignore_set.is_match(relative_path) || self.ignore_set.is_match(name)
+    }
+
+    pub fn entries_directory(&self) -> String {
+        format!("{}/{}", self.source_directory, self.directories.entries)
+    }
+
+    pub fn pages_directory(&self) -> String {
+        format!("{}/{}", self.source_directory, self.directories.pages)
+    }
+
+    pub fn fragments_directory(&self) -> String {
+        format!("{}/{}", self.source_directory, self.directories.fragments)
+    }
+
+    pub fn templates_directory(&self) -> String {
+        format!("{}/{}", self.source_directory, self.directories.templates)
+    }
+
+    pub fn feed_url(&self) -> String {
+        format!(
+            "{}/{}/{}",
+            self.base_url, self.output.blog, self.output.feed
+        )
+    }
+
+    /// Absolute URL of a tag's directory, without a trailing slash.
+    pub fn tag_url(&self, slug: &str) -> String {
+        format!(
+            "{}/{}/{}/{slug}",
+            self.base_url, self.output.blog, self.output.tags
+        )
+    }
+
+    pub fn feed_id(&self) -> String {
+        self.feed_id
+            .clone()
+            .unwrap_or_else(|| format!("{}/{}/", self.base_url, self.output.blog))
+    }
+}
@@ -0,0 +1,209 @@
+//! Dates as written in `meta.ron` and on the command line, and where they come from when
+//! `meta.ron` leaves them out.
+
+use std::{fmt, fs, process::Command, str::FromStr};
+
+use atom_syndication::FixedDateTime;
+use chrono::{
+    DateTime, NaiveDate, NaiveDateTime, NaiveTime, ParseError, SubsecRound, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+
+use crate::error::{IoResultExt, Result};
+
+/// A point in time, either with an explicit offset or in the site's timezone.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Timestamp {
+    /// A date ("2024-10-05", the start of that day) or a date and time without an offset
+    /// ("2024-10-05T18:30:00"), both in the site's timezone.
+    Local(NaiveDateTime),
+    /// An RFC 3339 timestamp, e.g. "2024-10-05T18:30:00+02:00".
+    Fixed(FixedDateTime),
+}
+
+impl FromStr for Timestamp {
+    type Err = ParseError;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+            return Ok(Self::Local(NaiveDateTime::new(date, NaiveTime::MIN)));
+        }
+        if let Ok(date_time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+            return Ok(Self::Local(date_time));
+        }
+        DateTime::parse_from_rfc3339(value).map(Self::Fixed)
+    }
+}
+
+impl Timestamp {
+    /// The timestamp with the offset it has in the given timezone.
+    pub fn in_timezone(&self, timezone: Tz) -> FixedDateTime {
+        match self {
+            Self::Local(date_time) => timezone
+                .from_local_datetime(date_time)
+                .earliest()
+                // Times skipped by a daylight saving transition are taken as UTC.
+                .unwrap_or_else(|| timezone.from_utc_datetime(date_time))
+                .fixed_offset(),
+            Self::Fixed(date_time) => date_time.with_timezone(&timezone).fixed_offset(),
+        }
+    }
+}
+
+/// Where a post's date was taken from.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum DateSource {
+    #[default]
+    Meta,
+    /// A commit touching the post's directory.
+    Git,
+    /// The creation or modification time of the post's files.
+    Files,
+}
+
+impl fmt::Display for DateSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Meta => "meta.ron",
+            Self::Git => "git history",
+            Self::Files => "file creation or modification time",
+        })
+    }
+}
+
+/// Which commit touching a directory to take the date of.
+#[derive(Clone, Copy)]
+pub enum Commit {
+    First,
+    Last,
+}
+
+/// Date of the first or last commit touching a directory, if it is tracked in a git repository.
+pub fn commit_date(directory: &str, commit: Commit, timezone: Tz) -> Option<FixedDateTime> {
+    let mut git = Command::new("git");
+    git.args(["-C", directory, "log", "--format=%cI"]);
+    if let Commit::Last = commit {
+        git.arg("-1");
+    }
+    // Without git or outside of a repository there simply is no history to fall back to.
+    let output = git.args(["--", "."]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let log = String::from_utf8_lossy(&output.stdout);
+    let line = match commit {
+        Commit::First => log.lines().last()?,
+        Commit::Last => log.lines().next()?,
+    };
+    let date = DateTime::parse_from_rfc3339(line.trim()).ok()?;
+    Some(Timestamp::Fixed(date).in_timezone(timezone))
+}
+
+/// Date of the files in a directory: the earliest creation or modification time for the
+/// first commit and the latest modification time for the last commit.
+pub fn file_date(directory: &str, commit: Commit, timezone: Tz) -> Result<FixedDateTime> {
+    let time = |metadata: fs::Metadata| match commit {
+        // Copies that keep their modification time are created after they were modified.
+        Commit::First => metadata
+            .modified()
+            .map(|modified| match metadata.created() {
+                Ok(created) => created.min(modified),
+                Err(_) => modified,
+            }),
+        Commit::Last => metadata.modified(),
+    };
+    let mut date = fs::metadata(directory)
+        .and_then(time)
+        .with_path(directory)?;
+    for file in fs::read_dir(directory).with_path(directory)? {
+        let file = file.with_path(directory)?;
+        let file_date = file
+            .metadata()
+            .and_then(time)
+            .with_path(&file.path().to_string_lossy())?;
+        date = match commit {
+            Commit::First => date.min(file_date),
+            Commit::Last => date.max(file_date),
+        };
+    }
+    Ok(DateTime::<Utc>::from(date)
+        .trunc_subsecs(0)
+        .with_timezone(&timezone)
+        .fixed_offset())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Europe::Berlin;
+
+    use super::*;
+
+    fn local(value: &str) -> Timestamp {
+        Timestamp::Local(NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").unwrap())
+    }
+
+    fn in_berlin(value: &str) -> String {
+        value
+            .parse::<Timestamp>()
+            .unwrap()
+            .in_timezone(Berlin)
+            .to_rfc3339()
+    }
+
+    #[test]
+    fn parses_dates_times_and_offsets() {
+        assert_eq!("2024-10-05".parse(), Ok(local("2024-10-05T00:00:00")));
+        assert_eq!(
+            "2024-10-05T18:30:00".parse(),
+            Ok(local("2024-10-05T18:30:00"))
+        );
+        assert_eq!(
+            "2024-10-05T18:30:00+02:00".parse(),
+            Ok(Timestamp::Fixed(
+                DateTime::parse_from_rfc3339("2024-10-05T18:30:00+02:00").unwrap()
+            ))
+        );
+        assert!("2024-10-05 18:30".parse::<Timestamp>().is_err());
+        assert!("2024-13-01".parse::<Timestamp>().is_err());
+        assert!("yesterday".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn local_times_take_the_offset_of_the_timezone() {
+        assert_eq!(in_berlin("2024-01-15"), "2024-01-15T00:00:00+01:00");
+        assert_eq!(
+            in_berlin("2024-07-15T18:30:00"),
+            "2024-07-15T18:30:00+02:00"
+        );
+    }
+
+    #[test]
+    fn fixed_times_keep_their_instant() {
+        assert_eq!(
+            in_berlin("2024-07-15T12:00:00-04:00"),
+            "2024-07-15T18:00:00+02:00"
+        );
+        assert_eq!(
+            in_berlin("2024-01-15T12:00:00Z"),
+            "2024-01-15T13:00:00+01:00"
+        );
+    }
+
+    #[test]
+    fn repeated_times_take_the_earlier_one() {
+        // Clocks go back from 3:00 to 2:00 on the last Sunday of October.
+        assert_eq!(
+            in_berlin("2024-10-27T02:30:00"),
+            "2024-10-27T02:30:00+02:00"
+        );
+    }
+
+    #[test]
+    fn skipped_times_are_taken_as_utc() {
+        // Clocks jump from 2:00 to 3:00 on the last Sunday of March.
+        assert_eq!(
+            in_berlin("2024-03-31T02:30:00"),
+            "2024-03-31T04:30:00+02:00"
+        );
+    }
+}
@@ -0,0 +1,99 @@
+use std::io;
+
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, ScrigenError>;
+
+#[derive(Debug, Error)]
+pub enum ScrigenError {
+    #[error("{path}: {source}")]
+    Io { path: String, source: io::Error },
+    #[error("{path}:{}:{}: {}", .source.position.line, .source.position.col, .source.code)]
+    Ron {
+        path: String,
+        source: ron::error::SpannedError,
+    },
+    #[error("{path}: invalid date '{value}' in field `{field}`: {source}")]
+    Date {
+        path: String,
+        field: &'static str,
+        value: String,
+        source: chrono::ParseError,
+    },
+    #[error("{path}: invalid ignore pattern '{pattern}': {source}")]
+    IgnorePattern {
+        path: String,
+        pattern: String,
+        source: globset::Error,
+    },
+    #[error("could not copy {source_path} to {target_path}: {source}")]
+    Copy {
+        source_path: String,
+        target_path: String,
+        source: io::Error,
+    },
+    #[error(
+        "{path}: page directories need a name and either a numeric prefix like `1_about` or an `order` in meta.ron"
+    )]
+    InvalidPageDirectory { path: String },
+    #[error("site.ron: the menu links to {target}, which isn't part of the site")]
+    UnknownMenuTarget { target: String },
+    #[error("'{title}' has no letters or digits to derive a directory name from")]
+    EmptySlug { title: String },
+    #[error("{path}: tag '{tag}' has no letters or digits to derive a URL from")]
+    EmptyTagSlug { path: String, tag: String },
+    #[error("{path}: tag '{tag}' has the same URL `{slug}` as tag '{other}', rename one of them")]
+    TagSlugClash {
+        path: String,
+        tag: String,
+        other: String,
+        slug: String,
+    },
+    #[error("the landing page has no page {number}, only pages 1 to {count}")]
+    NoLandingPage { number: usize, count: usize },
+    #[error("{0}")]
+    Template(#[from] minijinja::Error),
+    #[error("could not serve at {address}: {source}")]
+    Serve {
+        address: String,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("{path}: could not watch for changes: {source}")]
+    Watch { path: String, source: notify::Error },
+    #[error("{path}: {message}")]
+    Markdown { path: String, message: String },
+    #[error("{path}: could not highlight code block: {source}")]
+    Highlight {
+        path: String,
+        source: inkjet::InkjetError,
+    },
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+    Content(Vec<ScrigenError>),
+    /// Every asset that couldn't be copied, the others were copied regardless.
+    #[error("{}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("\n"))]
+    Assets(Vec<ScrigenError>),
+}
+
+impl ScrigenError {
+    /// Flattens collected content errors into a list of individual errors.
+    pub fn into_errors(self) -> Vec<ScrigenError> {
+        match self {
+            ScrigenError::Content(errors) | ScrigenError::Assets(errors) => errors,
+            error => vec![error],
+        }
+    }
+}
+
+/// Attaches the offending file path to I/O errors.
+pub(crate) trait IoResultExt<T> {
+    fn with_path(self, path: &str) -> Result<T>;
+}
+
+impl<T> IoResultExt<T> for io::Result<T> {
+    fn with_path(self, path: &str) -> Result<T> {
+        self.map_err(|source| ScrigenError::Io {
+            path: path.to_string(),
+            source,
+        })
+    }
+}
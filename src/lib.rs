@@ -0,0 +1,33 @@
+//! Small, bespoke static site generator for a personal blog.
+//!
+//! [`Site::load`] reads a source directory containing `site.ron`, blog posts and static pages,
+//! [`Site::build`] writes the generated site into a target directory.
+
+pub mod archive;
+mod assets;
+pub mod blog_post;
+mod cache;
+pub mod config;
+pub mod date;
+pub mod error;
+mod mdast_html;
+pub mod menu;
+pub mod neighbours;
+pub mod page;
+pub mod renderer;
+pub mod scaffold;
+pub mod serve;
+mod site;
+pub mod static_page;
+pub mod tags;
+pub mod templates;
+pub mod watch;
+
+pub use blog_post::BlogPost;
+pub use config::SiteConfig;
+pub use error::{Result, ScrigenError};
+pub use page::Page;
+pub use renderer::ContentRenderer;
+pub use site::{BuildOptions, Site, Step};
+pub use static_page::StaticPage;
+pub use templates::Templates;
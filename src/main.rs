@@ -1,307 +1,336 @@
-use atom_syndication::{Entry, Feed, Generator, Link, Person, Text};
-use serde::Deserialize;
-use static_page::StaticPage;
-use std::{
-    cmp::Ordering,
-    env,
-    fs::{self, File},
-    io::Write,
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use scrigen::{
+    date::Timestamp, scaffold, serve, watch, watch::Plan, BuildOptions, Result, ScrigenError, Site,
+    SiteConfig, Step,
 };
-
-mod blog_post;
-mod page;
-mod static_page;
-
-use blog_post::*;
-use page::Page;
+use std::{path::Path, process::ExitCode};
 
 // TODO:
-// - Publish and update dates in post
-// - Categories
-// - Proper error handling
 // - Proper OS directory handling
 
-#[derive(Deserialize)]
-struct FeedInfo {
-    title: String,
-    id: String,
-    author_name: String,
-    author_email: String,
-    author_uri: String,
-    link_site: String,
-    link_feed: String,
+/// Exit codes, documented in `--help`.
+const EXIT_FAILURE: u8 = 1;
+const EXIT_USAGE: u8 = 2;
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0  Success
+  1  Building or checking the site failed
+  2  Invalid command line arguments or source directory";
+
+/// Small, bespoke static site generator for a personal blog.
+#[derive(Parser)]
+#[command(version, about, after_help = EXIT_CODES_HELP)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Print details about the build, such as where each post's dates come from
+    #[arg(short, long, global = true)]
+    verbose: bool,
 }
 
-#[derive(PartialEq)]
-enum Operation {
-    WriteAll,
-    WriteLanding,
-    WritePosts,
-    WritePages,
-    WriteFeed,
+#[derive(Subcommand)]
+enum Command {
+    /// Build the site from a source directory into a target directory
+    Build {
+        /// Directory containing site.ron and the content directories
+        source: String,
+        /// Directory the generated site is written to
+        target: String,
+        /// Only run a single build step
+        #[arg(long, value_enum)]
+        only: Option<StepArg>,
+        /// Keep running and rebuild what is affected whenever the source changes
+        #[arg(long, conflicts_with = "only")]
+        watch: bool,
+        #[command(flatten)]
+        options: OptionsArgs,
+    },
+    /// Parse and render all content without writing anything
+    Check {
+        /// Directory containing site.ron and the content directories
+        source: String,
+        #[command(flatten)]
+        options: OptionsArgs,
+    },
+    /// Build the site into a temporary directory and serve it on localhost, rebuilding and
+    /// reloading the browser whenever the source changes
+    Serve {
+        /// Directory containing site.ron and the content directories
+        source: String,
+        /// Directory the site is usually built into, files the preview doesn't generate, like
+        /// stylesheets and fonts, are served from there
+        target: Option<String>,
+        /// Port to listen on
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+        #[command(flatten)]
+        options: OptionsArgs,
+    },
+    /// Create a new post or page with a pre-filled meta.ron and an empty content.md
+    New {
+        #[command(subcommand)]
+        kind: NewKind,
+        /// Directory containing site.ron and the content directories
+        #[arg(long, default_value = ".", global = true)]
+        source: String,
+    },
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+#[derive(Subcommand)]
+enum NewKind {
+    /// A draft post in the entries directory, dated today
+    Post {
+        /// Title of the post, its directory name is derived from it
+        title: String,
+    },
+    /// A page in the pages directory, ordered after the existing pages
+    Page {
+        /// Name of the page in the navigation, its directory name is derived from it
+        name: String,
+    },
+}
+
+/// Options deciding which posts are included.
+#[derive(Args)]
+struct OptionsArgs {
+    /// Include draft posts, marked with a banner
+    #[arg(long)]
+    drafts: bool,
+    /// Include posts with a publishing date in the future
+    #[arg(long)]
+    future: bool,
+    /// Publish posts up to this date instead of now, e.g. 2024-10-05 or 2024-10-05T18:00:00+02:00
+    #[arg(long, value_name = "DATE")]
+    now: Option<Timestamp>,
+}
 
-    let source_directory;
-    let target_directory;
-    let operation;
-    match args[1].as_str() {
-        "--landing" => {
-            operation = Operation::WriteLanding;
-            source_directory = &args[2];
-            target_directory = &args[3];
+impl From<OptionsArgs> for BuildOptions {
+    fn from(options: OptionsArgs) -> Self {
+        BuildOptions {
+            drafts: options.drafts,
+            future: options.future,
+            now: options.now,
         }
-        "--post" => {
-            operation = Operation::WritePosts;
-            source_directory = &args[2];
-            target_directory = &args[3];
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum StepArg {
+    /// Blog posts and their assets
+    Posts,
+    /// Static pages and their assets
+    Pages,
+    /// The landing page listing all posts
+    Landing,
+    /// The Atom feed
+    Feed,
+    /// Tag pages and the tag overview
+    Tags,
+    /// The archive of all posts and the pages of each year
+    Archive,
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Build {
+            source,
+            target,
+            only,
+            watch,
+            options,
+        } => {
+            if let Err(message) = validate_source(&source) {
+                eprintln!("error: {message}");
+                return ExitCode::from(EXIT_USAGE);
+            }
+            let options = options.into();
+            if watch {
+                return report(build_and_watch(&source, &target, &options, cli.verbose));
+            }
+            report(build(&source, &target, only, &options, cli.verbose))
         }
-        "--pages" => {
-            operation = Operation::WritePages;
-            source_directory = &args[2];
-            target_directory = &args[3];
+        Command::Check { source, options } => {
+            if let Err(message) = validate_source(&source) {
+                eprintln!("error: {message}");
+                return ExitCode::from(EXIT_USAGE);
+            }
+            report(check(&source, &options.into(), cli.verbose))
         }
-        "--feed" => {
-            operation = Operation::WriteFeed;
-            source_directory = &args[2];
-            target_directory = &args[3];
+        Command::Serve {
+            source,
+            target,
+            port,
+            options,
+        } => {
+            if let Err(message) = validate_source(&source) {
+                eprintln!("error: {message}");
+                return ExitCode::from(EXIT_USAGE);
+            }
+            report(serve::serve(
+                &source,
+                target.as_deref(),
+                &options.into(),
+                port,
+            ))
         }
-        _ => {
-            operation = Operation::WriteAll;
-            source_directory = &args[1];
-            target_directory = &args[2];
+        Command::New { kind, source } => {
+            if let Err(message) = validate_source(&source) {
+                eprintln!("error: {message}");
+                return ExitCode::from(EXIT_USAGE);
+            }
+            report(new(&source, kind))
         }
-    };
-
-    let static_pages: Vec<StaticPage> = parse_pages(&source_directory);
-    let blog_posts = parse_posts(source_directory);
-    if operation == Operation::WritePosts || operation == Operation::WriteAll {
-        write_posts(
-            &blog_posts,
-            &static_pages,
-            &source_directory,
-            &target_directory,
-        )
     }
-    if operation == Operation::WriteLanding || operation == Operation::WriteAll {
-        write_landing_page(
-            &blog_posts,
-            &static_pages,
-            &source_directory,
-            &target_directory,
-        );
+}
+
+fn report(result: Result<()>) -> ExitCode {
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(ScrigenError::Content(errors)) => {
+            for error in &errors {
+                eprintln!("error: {error}");
+            }
+            eprintln!(
+                "error: found {} problem(s) in the content, nothing was written",
+                errors.len()
+            );
+            ExitCode::from(EXIT_FAILURE)
+        }
+        Err(ScrigenError::Assets(errors)) => {
+            for error in &errors {
+                eprintln!("error: {error}");
+            }
+            eprintln!("error: could not copy {} asset(s)", errors.len());
+            ExitCode::from(EXIT_FAILURE)
+        }
+        Err(error) => {
+            eprintln!("error: {error}");
+            ExitCode::from(EXIT_FAILURE)
+        }
     }
-    if operation == Operation::WriteFeed || operation == Operation::WriteAll {
-        write_feed(
-            &blog_posts,
-            &static_pages,
-            &source_directory,
-            &target_directory,
-        );
+}
+
+fn validate_source(source: &str) -> std::result::Result<(), String> {
+    if !Path::new(source).is_dir() {
+        return Err(format!("source directory '{source}' does not exist"));
     }
-    if operation == Operation::WritePages || operation == Operation::WriteAll {
-        write_static_pages(&static_pages, source_directory, target_directory);
+    if !Path::new(source).join("site.ron").is_file() {
+        return Err(format!("source directory '{source}' has no site.ron"));
     }
+    Ok(())
 }
 
-fn parse_pages(path: &str) -> Vec<StaticPage> {
-    let mut pages: Vec<StaticPage> = Vec::new();
-    let paths = fs::read_dir(format!("{path}/pages/")).unwrap();
-    for path in paths {
-        let path = path.unwrap();
-        let mut page = StaticPage::from_path(path.path().to_str().unwrap().to_string());
-        let path_name = path.file_name().to_str().unwrap().to_string();
-        page.id = path_name[2..].to_string();
-        page.order = u8::from_str_radix(&path_name[0..1], 10).unwrap();
-        if page.id.starts_with(".") {
-            continue;
+impl From<StepArg> for Step {
+    fn from(step: StepArg) -> Self {
+        match step {
+            StepArg::Posts => Step::Posts,
+            StepArg::Pages => Step::Pages,
+            StepArg::Landing => Step::Landing,
+            StepArg::Feed => Step::Feed,
+            StepArg::Tags => Step::Tags,
+            StepArg::Archive => Step::Archive,
         }
-        pages.push(page);
     }
-    pages.sort_by(|entry1, entry2| -> Ordering { entry1.order.cmp(&entry2.order) });
-    pages
 }
 
-fn parse_posts(path: &str) -> Vec<BlogPost> {
-    let mut blog_posts: Vec<BlogPost> = Vec::new();
-    let paths = fs::read_dir(format!("{path}/entries/")).unwrap();
-    for path in paths {
-        let path = path.unwrap();
-        let mut post = BlogPost::from_path(path.path().to_str().unwrap().to_string());
-        post.id = path.file_name().to_str().unwrap().to_string();
-        if post.id.starts_with(".") {
-            continue;
-        }
-        blog_posts.push(post);
+fn build(
+    source_directory: &str,
+    target_directory: &str,
+    only: Option<StepArg>,
+    options: &BuildOptions,
+    verbose: bool,
+) -> Result<()> {
+    let site = Site::load_with_options(source_directory, options)?;
+    if verbose {
+        print_dates(&site);
+    }
+    match only {
+        Some(step) => site.build_step(step.into(), target_directory),
+        None => site.build(target_directory),
     }
-    blog_posts
-        .sort_by(|entry1, entry2| -> Ordering { entry2.published().cmp(&entry1.published()) });
-    blog_posts
 }
 
-fn write_posts(
-    blog_posts: &Vec<BlogPost>,
-    pages: &Vec<StaticPage>,
-    source_directory: &String,
-    target_directory: &String,
-) {
-    // TODO: Avoid reading this file twice
-    let feed_string = fs::read_to_string(format!("{source_directory}/feed.ron")).unwrap();
-    let feed_info: FeedInfo = ron::from_str(feed_string.as_str()).unwrap();
-    for post in blog_posts {
-        let html = post.to_html(
-            source_directory,
-            pages,
-            &feed_info.link_site,
-            &feed_info.title,
-        );
-        let directory = format!("{target_directory}/blog/{}", post.id);
-        let _result = fs::create_dir(directory.clone());
-        let _result = fs::write(directory + "/index.html", html);
-
-        for file in fs::read_dir(format!("{source_directory}/entries/{}", post.id)).unwrap() {
-            if let Ok(file) = file {
-                match file.file_name().to_str() {
-                    Some("content.md") => continue,
-                    Some("meta.ron") => continue,
-                    Some(file_name) => {
-                        let source_path =
-                            format!("{source_directory}/entries/{}/{}", post.id, file_name);
-                        let target_path =
-                            format!("{target_directory}/blog/{}/{}", post.id, file_name);
-                        let _result = fs::copy(source_path, target_path);
-                    }
-                    None => continue,
-                };
+fn build_and_watch(
+    source_directory: &str,
+    target_directory: &str,
+    options: &BuildOptions,
+    verbose: bool,
+) -> Result<()> {
+    if let Err(error) = build(source_directory, target_directory, None, options, verbose) {
+        print_errors(error);
+    }
+    println!("Watching {source_directory} for changes");
+    watch::watch(source_directory, |paths| {
+        let result = Site::load_with_options(source_directory, options).and_then(|site| {
+            let plan = Plan::for_changes(&site, paths)?;
+            if !plan.is_empty() {
+                plan.run(&site, target_directory)?;
+                println!("Rebuilt {plan}");
             }
+            Ok(())
+        });
+        if let Err(error) = result {
+            print_errors(error);
         }
-    }
+    })
 }
 
-fn write_static_pages(
-    pages: &Vec<StaticPage>,
-    source_directory: &String,
-    target_directory: &String,
-) {
-    let feed_string = fs::read_to_string(format!("{source_directory}/feed.ron")).unwrap();
-    let feed_info: FeedInfo = ron::from_str(feed_string.as_str()).unwrap();
-    for page in pages {
-        let html = page.to_html(
-            source_directory,
-            pages,
-            &feed_info.link_site,
-            &feed_info.title,
-        );
-        let directory = format!("{target_directory}/{}", page.id);
-        let _result = fs::create_dir(directory.clone());
-        let _result = fs::write(directory + "/index.html", html);
-        for file in fs::read_dir(format!(
-            "{source_directory}/pages/{}_{}",
-            page.order, page.id
-        ))
-        .unwrap()
-        {
-            if let Ok(file) = file {
-                match file.file_name().to_str() {
-                    Some("content.md") => continue,
-                    Some("meta.ron") => continue,
-                    Some(file_name) => {
-                        let source_path = format!(
-                            "{source_directory}/pages/{}_{}/{}",
-                            page.order, page.id, file_name
-                        );
-                        let target_path = format!("{target_directory}/{}/{}", page.id, file_name);
-                        let _result = fs::copy(source_path, target_path);
-                    }
-                    None => continue,
-                };
-            }
-        }
+fn print_errors(error: ScrigenError) {
+    for error in error.into_errors() {
+        eprintln!("error: {error}");
     }
 }
 
-fn write_landing_page(
-    blog_posts: &Vec<BlogPost>,
-    pages: &Vec<StaticPage>,
-    source_directory: &String,
-    target_directory: &String,
-) {
-    let landing_header =
-        fs::read_to_string(format!("{source_directory}/fragments/landing_header.html")).unwrap();
-    let mut page_links = String::from("<a href=\"./index.html\">Blog</a>");
-    for page in pages {
-        page_links += format!("<a href=\"{}/index.html\">{}</a>", page.id, page.name).as_str();
+fn check(source_directory: &str, options: &BuildOptions, verbose: bool) -> Result<()> {
+    let site = Site::load_with_options(source_directory, options)?;
+    if verbose {
+        print_dates(&site);
     }
-    let landing_header = landing_header.replace("___STATIC_PAGES___", &page_links);
-
-    let landing_footer =
-        fs::read_to_string(format!("{source_directory}/fragments/landing_footer.html")).unwrap();
-
-    let mut html = String::from(landing_header);
-    for post in blog_posts {
-        html += post.to_entry_fragment().as_str();
+    for post in site.posts() {
+        site.render_post(post)?;
+    }
+    for page in site.pages() {
+        site.render_page(page)?;
+    }
+    for number in 1..=site.landing_page_count() {
+        site.render_landing_page(number)?;
+    }
+    for tag in site.tags() {
+        site.render_tag_page(&tag)?;
+    }
+    site.render_tags_page()?;
+    for year in site.archive() {
+        site.render_year_page(&year)?;
     }
-    html += landing_footer.as_str();
-    let _result = fs::write(format!("{target_directory}/index.html"), html);
+    site.render_archive_page()?;
+    println!(
+        "Checked {} posts and {} pages.",
+        site.posts().len(),
+        site.pages().len()
+    );
+    Ok(())
 }
 
-fn write_feed(
-    blog_posts: &Vec<BlogPost>,
-    pages: &Vec<StaticPage>,
-    source_directory: &String,
-    target_directory: &String,
-) {
-    let feed_string = fs::read_to_string(format!("{source_directory}/feed.ron")).unwrap();
-    let feed_info: FeedInfo = ron::from_str(feed_string.as_str()).unwrap();
-
-    let mut feed = Feed {
-        title: Text::from(feed_info.title.clone()),
-        id: feed_info.id,
-        authors: vec![Person {
-            name: feed_info.author_name,
-            email: Some(feed_info.author_email),
-            uri: Some(feed_info.author_uri),
-        }],
-        generator: Some(Generator {
-            value: "atom_syndication".into(),
-            uri: Some("https://github.com/rust-syndication/atom".into()),
-            version: Some("0.12.4".into()),
-        }),
-        links: vec![
-            Link {
-                href: feed_info.link_site.clone(),
-                rel: "alternate".into(),
-                mime_type: Some("text/html".into()),
-                ..Default::default()
-            },
-            Link {
-                href: feed_info.link_feed.clone(),
-                rel: "self".into(),
-                mime_type: Some("application/atom+xml".into()),
-                ..Default::default()
-            },
-        ],
-        ..Default::default()
+fn new(source_directory: &str, kind: NewKind) -> Result<()> {
+    let config = SiteConfig::load(source_directory)?;
+    let directory = match kind {
+        NewKind::Post { title } => scaffold::new_post(&config, &title)?,
+        NewKind::Page { name } => scaffold::new_page(&config, &name)?,
     };
+    println!("Created {directory}");
+    Ok(())
+}
 
-    let mut entries: Vec<Entry> = blog_posts
-        .iter()
-        .map(|post| -> Entry {
-            post.get_atom_entry(
-                source_directory,
-                &pages,
-                &feed_info.link_site.clone(),
-                &feed_info.title.clone(),
-            )
-        })
-        .collect();
-
-    feed.set_entries(entries.clone());
-    entries.sort_by(|entry1, entry2| -> Ordering { entry2.updated().cmp(&entry1.updated()) });
-    feed.set_updated(*entries[0].updated());
-
-    let mut feed_file = File::create(format!("{target_directory}/blog/atom.xml")).unwrap();
-    let _result = feed_file.write_all(feed.to_string().as_bytes());
+fn print_dates(site: &Site) {
+    for post in site.posts() {
+        println!(
+            "{}: published {} (from {}), updated {} (from {})",
+            post.id,
+            post.published().to_rfc3339(),
+            post.published_source(),
+            post.updated().to_rfc3339(),
+            post.updated_source(),
+        );
+    }
 }
@@ -0,0 +1,429 @@
+//! Serializes the `markdown` crate's mdast to HTML, so that scrigen can transform the
+//! syntax tree of a whole document before writing it out.
+
+use std::collections::HashMap;
+
+use markdown::mdast::{AlignKind, Definition, FootnoteDefinition, ListItem, Node};
+
+/// Escapes text for use in HTML content and attribute values.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            _ => escaped.push(character),
+        }
+    }
+    escaped
+}
+
+/// Protocols allowed in links and images, like the `markdown` crate allows them.
+const LINK_PROTOCOLS: [&str; 6] = ["http", "https", "irc", "ircs", "mailto", "xmpp"];
+const IMAGE_PROTOCOLS: [&str; 2] = ["http", "https"];
+
+/// Encodes a URL for an attribute value, dropping it entirely if its protocol isn't one of
+/// `protocols`. Relative URLs are kept.
+fn sanitize_url(url: &str, protocols: &[&str]) -> String {
+    let url = markdown::sanitize(url);
+    let end = url.find(['?', '#', '/']).unwrap_or(url.len());
+    if let Some(colon) = url.find(':').filter(|&colon| colon < end) {
+        if !protocols.contains(&url[..colon].to_lowercase().as_str()) {
+            return String::new();
+        }
+    }
+    url
+}
+
+/// Concatenates the text of a node and its descendants, dropping all formatting.
+pub fn plain_text(nodes: &[Node]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            Node::Text(node) => text += &node.value,
+            Node::InlineCode(node) => text += &node.value,
+            Node::Image(node) => text += &node.alt,
+            _ => {
+                if let Some(children) = node.children() {
+                    text += &plain_text(children);
+                }
+            }
+        }
+    }
+    text
+}
+
+/// Writes mdast nodes as HTML, resolving references against the document's definitions
+/// and numbering footnotes in the order they are first referenced.
+pub struct HtmlWriter<'a> {
+    definitions: HashMap<String, &'a Definition>,
+    footnote_definitions: HashMap<String, &'a FootnoteDefinition>,
+    footnote_order: Vec<String>,
+}
+
+impl<'a> HtmlWriter<'a> {
+    pub fn new(root: &'a Node) -> Self {
+        let mut writer = Self {
+            definitions: HashMap::new(),
+            footnote_definitions: HashMap::new(),
+            footnote_order: Vec::new(),
+        };
+        writer.collect_definitions(root);
+        writer
+    }
+
+    fn collect_definitions(&mut self, node: &'a Node) {
+        match node {
+            Node::Definition(definition) => {
+                self.definitions
+                    .entry(definition.identifier.clone())
+                    .or_insert(definition);
+            }
+            Node::FootnoteDefinition(definition) => {
+                self.footnote_definitions
+                    .entry(definition.identifier.clone())
+                    .or_insert(definition);
+            }
+            _ => {}
+        }
+        if let Some(children) = node.children() {
+            for child in children {
+                self.collect_definitions(child);
+            }
+        }
+    }
+
+    pub fn write_nodes(&mut self, nodes: &[Node], html: &mut String) {
+        for node in nodes {
+            self.write(node, html);
+        }
+    }
+
+    pub fn write(&mut self, node: &Node, html: &mut String) {
+        match node {
+            Node::Root(node) => self.write_nodes(&node.children, html),
+            Node::Paragraph(node) => {
+                *html += "<p>";
+                self.write_nodes(&node.children, html);
+                *html += "</p>\n";
+            }
+            Node::Heading(node) => {
+                *html += &format!("<h{}>", node.depth);
+                self.write_nodes(&node.children, html);
+                *html += &format!("</h{}>\n", node.depth);
+            }
+            Node::ThematicBreak(_) => *html += "<hr />\n",
+            Node::Blockquote(node) => {
+                *html += "<blockquote>\n";
+                self.write_nodes(&node.children, html);
+                *html += "</blockquote>\n";
+            }
+            Node::List(node) => {
+                let loose = node.spread
+                    || node
+                        .children
+                        .iter()
+                        .any(|child| matches!(child, Node::ListItem(item) if item.spread));
+                match (node.ordered, node.start) {
+                    (true, Some(start)) if start != 1 => {
+                        *html += &format!("<ol start=\"{start}\">\n")
+                    }
+                    (true, _) => *html += "<ol>\n",
+                    (false, _) => *html += "<ul>\n",
+                }
+                for child in &node.children {
+                    match child {
+                        Node::ListItem(item) => self.write_list_item(item, loose, html),
+                        _ => self.write(child, html),
+                    }
+                }
+                *html += if node.ordered { "</ol>\n" } else { "</ul>\n" };
+            }
+            Node::Code(node) => {
+                match node.lang.as_deref() {
+                    Some(lang) => {
+                        *html += &format!("<pre><code class=\"language-{}\">", escape(lang))
+                    }
+                    None => *html += "<pre><code>",
+                }
+                *html += &escape(&node.value);
+                if !node.value.is_empty() {
+                    *html += "\n";
+                }
+                *html += "</code></pre>\n";
+            }
+            // Raw HTML from the content has been turned into text by this point,
+            // what's left was inserted by scrigen's own transforms.
+            Node::Html(node) => *html += &node.value,
+            Node::Table(node) => {
+                *html += "<table>\n";
+                for (index, row) in node.children.iter().enumerate() {
+                    if index == 0 {
+                        *html += "<thead>\n";
+                    } else if index == 1 {
+                        *html += "<tbody>\n";
+                    }
+                    *html += "<tr>\n";
+                    if let Some(cells) = row.children() {
+                        for (column, cell) in cells.iter().enumerate() {
+                            let tag = if index == 0 { "th" } else { "td" };
+                            match node.align.get(column) {
+                                Some(AlignKind::Left) => {
+                                    *html += &format!("<{tag} align=\"left\">")
+                                }
+                                Some(AlignKind::Right) => {
+                                    *html += &format!("<{tag} align=\"right\">")
+                                }
+                                Some(AlignKind::Center) => {
+                                    *html += &format!("<{tag} align=\"center\">")
+                                }
+                                _ => *html += &format!("<{tag}>"),
+                            }
+                            if let Some(children) = cell.children() {
+                                self.write_nodes(children, html);
+                            }
+                            *html += &format!("</{tag}>\n");
+                        }
+                    }
+                    *html += "</tr>\n";
+                    if index == 0 {
+                        *html += "</thead>\n";
+                    }
+                }
+                if node.children.len() > 1 {
+                    *html += "</tbody>\n";
+                }
+                *html += "</table>\n";
+            }
+            Node::Text(node) => *html += &escape(&node.value),
+            Node::Emphasis(node) => {
+                *html += "<em>";
+                self.write_nodes(&node.children, html);
+                *html += "</em>";
+            }
+            Node::Strong(node) => {
+                *html += "<strong>";
+                self.write_nodes(&node.children, html);
+                *html += "</strong>";
+            }
+            Node::Delete(node) => {
+                *html += "<del>";
+                self.write_nodes(&node.children, html);
+                *html += "</del>";
+            }
+            Node::InlineCode(node) => *html += &format!("<code>{}</code>", escape(&node.value)),
+            Node::Break(_) => *html += "<br />\n",
+            Node::Link(node) => {
+                self.write_link(&node.url, node.title.as_deref(), &node.children, html)
+            }
+            Node::Image(node) => write_image(&node.url, node.title.as_deref(), &node.alt, html),
+            Node::LinkReference(node) => match self.definitions.get(&node.identifier) {
+                Some(definition) => {
+                    let (url, title) = (definition.url.clone(), definition.title.clone());
+                    self.write_link(&url, title.as_deref(), &node.children, html)
+                }
+                None => self.write_nodes(&node.children, html),
+            },
+            Node::ImageReference(node) => match self.definitions.get(&node.identifier) {
+                Some(definition) => write_image(
+                    &definition.url,
+                    definition.title.as_deref(),
+                    &node.alt,
+                    html,
+                ),
+                None => *html += &escape(&node.alt),
+            },
+            Node::FootnoteReference(node) => self.write_footnote_reference(&node.identifier, html),
+            // Definitions are resolved where they are referenced, anything else
+            // (math, frontmatter, MDX) isn't enabled in the parse options.
+            _ => {}
+        }
+    }
+
+    fn write_list_item(&mut self, item: &ListItem, loose: bool, html: &mut String) {
+        *html += "<li>";
+        if let Some(checked) = item.checked {
+            *html += if checked {
+                "<input type=\"checkbox\" disabled=\"\" checked=\"\" /> "
+            } else {
+                "<input type=\"checkbox\" disabled=\"\" /> "
+            };
+        }
+        for (index, child) in item.children.iter().enumerate() {
+            match child {
+                Node::Paragraph(paragraph) if !loose => {
+                    self.write_nodes(&paragraph.children, html);
+                    if index + 1 < item.children.len() {
+                        *html += "\n";
+                    }
+                }
+                _ => {
+                    if index == 0 {
+                        *html += "\n";
+                    }
+                    self.write(child, html);
+                }
+            }
+        }
+        *html += "</li>\n";
+    }
+
+    fn write_link(&mut self, url: &str, title: Option<&str>, children: &[Node], html: &mut String) {
+        *html += &format!("<a href=\"{}\"", sanitize_url(url, &LINK_PROTOCOLS));
+        if let Some(title) = title {
+            *html += &format!(" title=\"{}\"", escape(title));
+        }
+        *html += ">";
+        self.write_nodes(children, html);
+        *html += "</a>";
+    }
+
+    fn write_footnote_reference(&mut self, identifier: &str, html: &mut String) {
+        if !self.footnote_definitions.contains_key(identifier) {
+            *html += &format!("[^{}]", escape(identifier));
+            return;
+        }
+        let number = match self.footnote_order.iter().position(|id| id == identifier) {
+            Some(index) => index + 1,
+            None => {
+                self.footnote_order.push(identifier.to_string());
+                self.footnote_order.len()
+            }
+        };
+        *html += &format!(
+            "<sup><a href=\"#fn-{number}\" id=\"fnref-{number}\" data-footnote-ref=\"\" aria-describedby=\"footnote-label\">{number}</a></sup>"
+        );
+    }
+
+    /// Writes the definitions of all referenced footnotes, if there are any.
+    pub fn write_footnotes(&mut self, html: &mut String) {
+        if self.footnote_order.is_empty() {
+            return;
+        }
+        *html += "<section data-footnotes=\"\" class=\"footnotes\"><h2 id=\"footnote-label\" class=\"sr-only\">Footnotes</h2>\n<ol>\n";
+        // Footnote definitions can reference further footnotes, which are appended while writing.
+        let mut index = 0;
+        while index < self.footnote_order.len() {
+            let number = index + 1;
+            let definition = self.footnote_definitions[&self.footnote_order[index]];
+            *html += &format!("<li id=\"fn-{number}\">\n");
+            let mut content = String::new();
+            self.write_nodes(&definition.children, &mut content);
+            let backref = format!(
+                " <a href=\"#fnref-{number}\" data-footnote-backref=\"\" aria-label=\"Back to reference {number}\" class=\"data-footnote-backref\">↩</a>"
+            );
+            match content.rfind("</p>") {
+                Some(end) => content.insert_str(end, &backref),
+                None => content += &backref,
+            }
+            *html += content.trim_end();
+            *html += "\n</li>\n";
+            index += 1;
+        }
+        *html += "</ol>\n</section>\n";
+    }
+}
+
+fn write_image(url: &str, title: Option<&str>, alt: &str, html: &mut String) {
+    *html += &format!(
+        "<img src=\"{}\" alt=\"{}\"",
+        sanitize_url(url, &IMAGE_PROTOCOLS),
+        escape(alt)
+    );
+    if let Some(title) = title {
+        *html += &format!(" title=\"{}\"", escape(title));
+    }
+    *html += " />";
+}
+
+#[cfg(test)]
+mod tests {
+    use markdown::{Options, ParseOptions};
+
+    use super::*;
+
+    fn write_html(markdown: &str) -> String {
+        let root = markdown::to_mdast(markdown, &ParseOptions::gfm()).unwrap();
+        let mut writer = HtmlWriter::new(&root);
+        let mut html = String::new();
+        writer.write(&root, &mut html);
+        writer.write_footnotes(&mut html);
+        html
+    }
+
+    /// The writer renders like the `markdown` crate itself.
+    fn assert_like_markdown(markdown: &str) {
+        let expected = markdown::to_html_with_options(markdown, &Options::gfm()).unwrap();
+        assert_eq!(write_html(markdown).trim_end(), expected.trim_end());
+    }
+
+    #[test]
+    fn writes_blocks() {
+        assert_like_markdown(
+            "# Title\n\nSome *emphasis*, **strong** and ~~deleted~~ text.\n\n---\n",
+        );
+        assert_like_markdown("> Quoted\n> text\n");
+        assert_like_markdown("```rust\nfn main() {}\n```\n\n```\n```\n");
+        assert_like_markdown("Line  \nbreak and `code`\n");
+    }
+
+    #[test]
+    fn writes_lists() {
+        assert_like_markdown("- one\n- two\n  - nested\n");
+        assert_like_markdown("3. three\n4. four\n");
+        assert_like_markdown("- loose\n\n- list\n");
+        assert_like_markdown("- [ ] open\n- [x] done\n");
+        assert_like_markdown("1. item\n\n   with two paragraphs\n");
+    }
+
+    #[test]
+    fn writes_tables() {
+        assert_like_markdown("| a | b | c | d |\n| - | :- | -: | :-: |\n| 1 | 2 | 3 | 4 |\n");
+        assert_like_markdown("| only | header |\n| --- | --- |\n");
+    }
+
+    #[test]
+    fn writes_links_and_images() {
+        assert_like_markdown("[inline](https://example.org \"Title\") and ![alt](a.png \"T\")\n");
+        assert_like_markdown("[reference][ref], [collapsed][] and ![image][ref]\n\n[ref]: https://example.org\n[collapsed]: /x\n");
+        assert_like_markdown("[missing][nowhere]\n");
+        assert_like_markdown("<https://example.org> and www.example.org\n");
+        assert_like_markdown("[unsafe](javascript:alert(1))\n");
+    }
+
+    /// Footnotes are numbered in the order they are referenced, with ids made of their number.
+    #[test]
+    fn writes_footnotes() {
+        let reference = |number: usize| {
+            format!("<sup><a href=\"#fn-{number}\" id=\"fnref-{number}\" data-footnote-ref=\"\" aria-describedby=\"footnote-label\">{number}</a></sup>")
+        };
+        let backref = |number: usize| {
+            format!(" <a href=\"#fnref-{number}\" data-footnote-backref=\"\" aria-label=\"Back to reference {number}\" class=\"data-footnote-backref\">↩</a>")
+        };
+        assert_eq!(
+            write_html("Text[^b] and more[^a], not[^none].\n\n[^a]: First.\n[^b]: Second.\n"),
+            format!(
+                "<p>Text{} and more{}, not[^none].</p>\n\
+                 <section data-footnotes=\"\" class=\"footnotes\"><h2 id=\"footnote-label\" class=\"sr-only\">Footnotes</h2>\n\
+                 <ol>\n<li id=\"fn-1\">\n<p>Second.{}</p>\n</li>\n<li id=\"fn-2\">\n<p>First.{}</p>\n</li>\n</ol>\n</section>\n",
+                reference(1),
+                reference(2),
+                backref(1),
+                backref(2),
+            )
+        );
+        assert_eq!(write_html("No notes.\n"), "<p>No notes.</p>\n");
+    }
+
+    #[test]
+    fn escapes_text_and_attributes() {
+        assert_like_markdown("5 < 6 & \"quotes\" > 'single'\n");
+        assert_like_markdown("[a](/x \"<title> & \\\"quotes\\\"\") `<code>`\n");
+        assert_eq!(
+            escape("<a href=\"x\">&</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! The navigation menu shown on every page: the items configured as `menu` in `site.ron`, or
+//! the blog followed by the pages when there are none.
+
+use std::fmt;
+
+use minijinja::{context, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::ScrigenError, site::root_of, tags::slug, Site};
+
+/// Ordered menu items, each optionally with a dropdown of further items.
+#[derive(Deserialize, Serialize, Clone, Default, Debug)]
+#[serde(transparent)]
+pub struct Menu {
+    pub items: Vec<MenuItem>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct MenuItem {
+    /// Text of the link, defaults to the name of the page or tag it points at.
+    #[serde(default)]
+    pub label: Option<String>,
+    pub target: MenuTarget,
+    /// Items shown in a dropdown below this one.
+    #[serde(default)]
+    pub children: Vec<MenuItem>,
+}
+
+/// What a menu item links to.
+#[derive(Deserialize, Serialize, Clone, PartialEq, Eq, Debug)]
+pub enum MenuTarget {
+    /// The landing page, active on every page of the blog.
+    Blog,
+    /// The overview of all tags.
+    Tags,
+    /// The archive of all posts, active on the pages of each year as well.
+    Archive,
+    /// A page by its id, e.g. `Page("projects/tools")`.
+    Page(String),
+    /// A tag's page by the tag's name.
+    Tag(String),
+    /// Any other address, never active.
+    Url(String),
+}
+
+impl fmt::Display for MenuTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MenuTarget::Blog => f.write_str("the blog"),
+            MenuTarget::Tags => f.write_str("the tags overview"),
+            MenuTarget::Archive => f.write_str("the archive"),
+            MenuTarget::Page(id) => write!(f, "page '{id}'"),
+            MenuTarget::Tag(name) => write!(f, "tag '{name}'"),
+            MenuTarget::Url(url) => f.write_str(url),
+        }
+    }
+}
+
+impl Menu {
+    /// The blog followed by the top-level pages, each with the pages nested in it as dropdown.
+    pub fn from_pages(site: &Site) -> Self {
+        fn page_items(site: &Site, parent: Option<&str>) -> Vec<MenuItem> {
+            site.pages()
+                .iter()
+                .filter(|page| page.parent.as_deref() == parent)
+                .map(|page| MenuItem {
+                    label: None,
+                    target: MenuTarget::Page(page.id.clone()),
+                    children: page_items(site, Some(&page.id)),
+                })
+                .collect()
+        }
+        let mut items = vec![MenuItem {
+            label: None,
+            target: MenuTarget::Blog,
+            children: Vec::new(),
+        }];
+        items.extend(page_items(site, None));
+        Self { items }
+    }
+
+    /// Errors for every item pointing at a page or tag the site doesn't have. Tags only used by
+    /// drafts or scheduled posts are fine, their items are left out until the tag is built.
+    pub fn check(&self, site: &Site) -> Vec<ScrigenError> {
+        fn check_items(items: &[MenuItem], site: &Site, errors: &mut Vec<ScrigenError>) {
+            for item in items {
+                if !item.target.exists(site) {
+                    errors.push(ScrigenError::UnknownMenuTarget {
+                        target: item.target.to_string(),
+                    });
+                }
+                check_items(&item.children, site, errors);
+            }
+        }
+        let mut errors = Vec::new();
+        check_items(&self.items, site, &mut errors);
+        errors
+    }
+
+    /// The menu as seen by templates on the page written to the directory `current`, relative
+    /// to the site root with a trailing slash, e.g. `blog/first-post/`, or empty for the landing
+    /// page. Items get a `label`, an `href` relative to that page, whether they are `external`,
+    /// their `children` and whether they are `active`: the items whose section contains the
+    /// current page most closely, and the items containing those in their dropdown. Items
+    /// pointing at tags this build doesn't write are left out.
+    pub fn context(&self, site: &Site, current: &str) -> Vec<Value> {
+        fn closest(items: &[MenuItem], site: &Site, current: &str) -> Option<usize> {
+            items
+                .iter()
+                .flat_map(|item| {
+                    [
+                        item.target.section_match(site, current),
+                        closest(&item.children, site, current),
+                    ]
+                })
+                .flatten()
+                .max()
+        }
+        let root = root_of(current);
+        let closest = closest(&self.items, site, current);
+        self.items
+            .iter()
+            .filter(|item| item.target.is_built(site))
+            .map(|item| item.context(site, current, &root, closest).0)
+            .collect()
+    }
+}
+
+impl MenuItem {
+    /// The item as seen by templates and whether it or one of its children is active.
+    fn context(
+        &self,
+        site: &Site,
+        current: &str,
+        root: &str,
+        closest: Option<usize>,
+    ) -> (Value, bool) {
+        let (children, child_active): (Vec<Value>, Vec<bool>) = self
+            .children
+            .iter()
+            .filter(|child| child.target.is_built(site))
+            .map(|child| child.context(site, current, root, closest))
+            .unzip();
+        let active = child_active.contains(&true)
+            || (closest.is_some() && self.target.section_match(site, current) == closest);
+        let context = context! {
+            label => self.label.clone().unwrap_or_else(|| self.target.default_label(site)),
+            href => self.target.href(site, root),
+            external => matches!(self.target, MenuTarget::Url(_)),
+            active => active,
+            children => children,
+        };
+        (context, active)
+    }
+}
+
+impl MenuTarget {
+    fn exists(&self, site: &Site) -> bool {
+        let features = &site.config().features;
+        match self {
+            MenuTarget::Blog | MenuTarget::Url(_) => true,
+            MenuTarget::Tags => features.tags,
+            MenuTarget::Archive => features.archive,
+            MenuTarget::Page(id) => site.pages().iter().any(|page| page.id == *id),
+            MenuTarget::Tag(name) => features.tags && site.has_tag(name),
+        }
+    }
+
+    /// Whether the target is written by this build, which leaves out tags of drafts.
+    fn is_built(&self, site: &Site) -> bool {
+        match self {
+            MenuTarget::Tag(name) => site.tags().iter().any(|tag| tag.slug == slug(name)),
+            _ => true,
+        }
+    }
+
+    fn default_label(&self, site: &Site) -> String {
+        match self {
+            MenuTarget::Blog => "Blog".into(),
+            MenuTarget::Tags => "Tags".into(),
+            MenuTarget::Archive => "Archive".into(),
+            MenuTarget::Page(id) => site
+                .pages()
+                .iter()
+                .find(|page| page.id == *id)
+                .map_or_else(|| id.clone(), |page| page.name.clone()),
+            MenuTarget::Tag(name) => site
+                .tags()
+                .into_iter()
+                .find(|tag| tag.slug == slug(name))
+                .map_or_else(|| name.clone(), |tag| tag.name),
+            MenuTarget::Url(url) => url.clone(),
+        }
+    }
+
+    /// Directory of the target relative to the site root, with a trailing slash.
+    fn directory(&self, site: &Site) -> Option<String> {
+        let output = &site.config().output;
+        match self {
+            MenuTarget::Blog => Some(String::new()),
+            MenuTarget::Tags => Some(format!("{}/{}/", output.blog, output.tags)),
+            MenuTarget::Archive => Some(format!("{}/{}/", output.blog, output.archive)),
+            MenuTarget::Page(id) => Some(format!("{id}/")),
+            MenuTarget::Tag(name) => {
+                Some(format!("{}/{}/{}/", output.blog, output.tags, slug(name)))
+            }
+            MenuTarget::Url(_) => None,
+        }
+    }
+
+    fn href(&self, site: &Site, root: &str) -> String {
+        match (self, self.directory(site)) {
+            (MenuTarget::Url(url), _) => url.clone(),
+            (_, directory) => format!("{root}{}index.html", directory.unwrap_or_default()),
+        }
+    }
+
+    /// How closely the target's section contains the page in `current`, longer is closer. The
+    /// blog's section is the landing page with all of its pages and everything in the blog
+    /// directory, the archive's also includes the pages of single years.
+    fn section_match(&self, site: &Site, current: &str) -> Option<usize> {
+        let output = &site.config().output;
+        let blog = format!("{}/", output.blog);
+        let section = match self {
+            MenuTarget::Blog
+                if current.is_empty()
+                    || current.starts_with(&format!("{}/", output.pagination)) =>
+            {
+                return Some(0)
+            }
+            MenuTarget::Blog => blog,
+            // The pages of single years belong to the archive.
+            MenuTarget::Archive
+                if current
+                    .strip_prefix(&blog)
+                    .and_then(|rest| rest.strip_suffix('/'))
+                    .is_some_and(|year| year.parse::<i32>().is_ok()) =>
+            {
+                return Some(current.len());
+            }
+            target => target.directory(site)?,
+        };
+        current.starts_with(&section).then_some(section.len())
+    }
+}
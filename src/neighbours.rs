@@ -0,0 +1,139 @@
+//! Links from a post to the posts published right before and after it, among all posts, in
+//! its series and with some of its tags.
+
+use minijinja::{context, Value};
+
+use crate::{blog_post::BlogPost, config::SiteConfig, tags::slug};
+
+/// The post published right before and the one published right after a post.
+#[derive(Default)]
+pub struct Neighbours<'a> {
+    pub previous: Option<&'a BlogPost>,
+    pub next: Option<&'a BlogPost>,
+}
+
+impl<'a> Neighbours<'a> {
+    /// The neighbours of `post` among `posts`, which are sorted newest first like the site's.
+    fn among(posts: &[&'a BlogPost], post: &BlogPost) -> Self {
+        let Some(index) = posts.iter().position(|other| other.id == post.id) else {
+            return Self::default();
+        };
+        Self {
+            previous: posts.get(index + 1).copied(),
+            next: index.checked_sub(1).map(|index| posts[index]),
+        }
+    }
+
+    fn context(&self, config: &SiteConfig) -> Value {
+        context! {
+            previous => self.previous.map(|post| post.context(config)),
+            next => self.next.map(|post| post.context(config)),
+        }
+    }
+}
+
+/// Neighbours of a post inside its series.
+pub struct SeriesNeighbours<'a> {
+    pub name: String,
+    /// Position of the post in the series, from 1 for its first post.
+    pub position: usize,
+    pub count: usize,
+    pub neighbours: Neighbours<'a>,
+}
+
+/// Neighbours of a post among the posts with one of its tags.
+pub struct TagNeighbours<'a> {
+    pub name: String,
+    pub neighbours: Neighbours<'a>,
+}
+
+/// Everything a post links to at its end.
+pub struct PostNeighbours<'a> {
+    pub chronological: Neighbours<'a>,
+    /// Only for posts in a series, if series navigation is turned on.
+    pub series: Option<SeriesNeighbours<'a>>,
+    /// One for every tag of the post listed in the site's `post_navigation`.
+    pub tags: Vec<TagNeighbours<'a>>,
+}
+
+impl<'a> PostNeighbours<'a> {
+    pub fn new(posts: &'a [BlogPost], post: &BlogPost, config: &SiteConfig) -> Self {
+        let all: Vec<&BlogPost> = posts.iter().collect();
+        let series = post
+            .series
+            .as_ref()
+            .filter(|_| config.post_navigation.series)
+            .map(|name| {
+                let in_series: Vec<&BlogPost> = posts
+                    .iter()
+                    .filter(|other| {
+                        other
+                            .series
+                            .as_deref()
+                            .is_some_and(|other| slug(other) == slug(name))
+                    })
+                    .collect();
+                let index = in_series
+                    .iter()
+                    .position(|other| other.id == post.id)
+                    .unwrap_or_default();
+                SeriesNeighbours {
+                    name: name.clone(),
+                    position: in_series.len() - index,
+                    count: in_series.len(),
+                    neighbours: Neighbours::among(&in_series, post),
+                }
+            });
+        let tags = post
+            .tags
+            .iter()
+            .filter(|name| {
+                config
+                    .post_navigation
+                    .tags
+                    .iter()
+                    .any(|tag| slug(tag) == slug(name))
+            })
+            .map(|name| {
+                let tagged: Vec<&BlogPost> = posts
+                    .iter()
+                    .filter(|other| other.tags.iter().any(|tag| slug(tag) == slug(name)))
+                    .collect();
+                TagNeighbours {
+                    name: name.clone(),
+                    neighbours: Neighbours::among(&tagged, post),
+                }
+            })
+            .collect();
+        Self {
+            chronological: Neighbours::among(&all, post),
+            series,
+            tags,
+        }
+    }
+
+    /// The links as seen by templates: the chronologically `previous` and `next` post, the
+    /// `series` with its `name`, the post's `position`, the `count` of its posts and its
+    /// `previous` and `next` post, and `tags`, each with its `name`, `slug`, `previous` and
+    /// `next` post.
+    pub fn context(&self, config: &SiteConfig) -> Value {
+        context! {
+            series => self.series.as_ref().map(|series| context! {
+                name => series.name,
+                position => series.position,
+                count => series.count,
+                ..series.neighbours.context(config)
+            }),
+            tags => self
+                .tags
+                .iter()
+                .map(|tag| context! {
+                    name => tag.name,
+                    slug => slug(&tag.name),
+                    ..tag.neighbours.context(config)
+                })
+                .collect::<Vec<_>>(),
+            ..self.chronological.context(config)
+        }
+    }
+}
@@ -1,6 +1,42 @@
-use crate::StaticPage;
+use std::fs;
 
-pub trait Page {
-    fn from_path(path: String) -> Self;
-    fn to_html(&self, source_dir: &String, pages: &Vec<StaticPage>, url_base: &String, site_name: &String) -> String;
-}
\ No newline at end of file
+use ron::extensions::Extensions;
+use serde::de::DeserializeOwned;
+
+use crate::{
+    config::SiteConfig,
+    error::{IoResultExt, Result, ScrigenError},
+    renderer::{ContentRenderer, RenderedContent},
+    Site,
+};
+
+pub trait Page: Sized {
+    fn from_path(path: String, config: &SiteConfig) -> Result<Self>;
+    fn markdown(&self) -> &str;
+    /// Directory the page was read from.
+    fn source_path(&self) -> &str;
+
+    /// Renders the page's Markdown content with the shared [`ContentRenderer`].
+    fn render_content(&self) -> Result<RenderedContent> {
+        ContentRenderer::new().render(
+            self.markdown(),
+            &format!("{}/content.md", self.source_path()),
+        )
+    }
+
+    /// Renders the complete HTML document of the page with the site's templates.
+    fn to_html(&self, site: &Site) -> Result<String>;
+}
+
+/// Reads and deserializes a RON file, reporting the file and position of syntax errors.
+/// Optional fields can be written without wrapping them in `Some(...)`.
+pub fn read_ron<T: DeserializeOwned>(path: &str) -> Result<T> {
+    let string = fs::read_to_string(path).with_path(path)?;
+    ron::Options::default()
+        .with_default_extension(Extensions::IMPLICIT_SOME)
+        .from_str(&string)
+        .map_err(|source| ScrigenError::Ron {
+            path: path.to_string(),
+            source,
+        })
+}
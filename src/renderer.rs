@@ -0,0 +1,369 @@
+use convert_case::Casing;
+use inkjet::{formatter, Highlighter};
+use regex::{Captures, Regex};
+
+use markdown::{
+    mdast::{Html, Node, Text},
+    ParseOptions,
+};
+
+use crate::{
+    error::{Result, ScrigenError},
+    mdast_html::{escape, plain_text, HtmlWriter},
+};
+
+/// A `<h2>` section of rendered content, listed in the sidebar.
+pub struct Section {
+    pub title: String,
+    pub id: String,
+}
+
+pub struct RenderedContent {
+    pub html: String,
+    pub sections: Vec<Section>,
+}
+
+/// Renders the Markdown of posts and pages, including scrigen's directives:
+/// - `!insert <markdown>` and `!insert bg <markdown>` break out of the text column,
+///   optionally followed by an `!image_subtitle <text>` line
+/// - `!html <html>` inserts raw HTML
+/// - code blocks marked as `GDScript` are syntax highlighted
+pub struct ContentRenderer {
+    highlighter: Highlighter,
+    symbol_regex: Regex,
+}
+
+impl Default for ContentRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContentRenderer {
+    pub fn new() -> Self {
+        Self {
+            highlighter: Highlighter::new(),
+            symbol_regex: Regex::new(
+                "(\\(|\\)|\\[|\\]|\\:|\\+|\\-)|\\*|\\{|\\}|&gt;|&#x2f;|&equals;",
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Renders `markdown`, read from `source_path`, which is only used for error messages.
+    ///
+    /// The document is parsed as a whole, code blocks and directives are then applied as
+    /// transforms of its syntax tree before it is written out as HTML.
+    pub fn render(&mut self, markdown: &str, source_path: &str) -> Result<RenderedContent> {
+        let source = isolate_directives(markdown);
+        let mut root = markdown::to_mdast(&source, &ParseOptions::gfm()).map_err(|message| {
+            ScrigenError::Markdown {
+                path: source_path.to_string(),
+                message: message.to_string(),
+            }
+        })?;
+        escape_raw_html(&mut root);
+        self.highlight_code_blocks(&mut root)
+            .map_err(|source| ScrigenError::Highlight {
+                path: source_path.to_string(),
+                source,
+            })?;
+        if let Some(children) = root.children_mut() {
+            *children = apply_directives(std::mem::take(children), &source);
+        }
+
+        let mut writer = HtmlWriter::new(&root);
+        let mut html = String::new();
+        let mut sections: Vec<Section> = vec![];
+        for node in root.children().into_iter().flatten() {
+            match node {
+                Node::Heading(heading) if heading.depth == 2 => {
+                    let mut section_title = String::new();
+                    writer.write_nodes(&heading.children, &mut section_title);
+                    let section_id: String = plain_text(&heading.children)
+                        .chars()
+                        .filter(|&c| c.is_alphanumeric() || c == ' ')
+                        .collect();
+                    let section_id = section_id.to_case(convert_case::Case::Snake);
+                    html += format!(
+                        "
+                <h2 id='{}'>{}<a href='#{}'><div class='section-link' alt='Section link'>
+                </div></a></h2>",
+                        section_id, section_title, section_id
+                    )
+                    .as_str();
+                    sections.push(Section {
+                        title: section_title,
+                        id: section_id,
+                    });
+                }
+                _ => writer.write(node, &mut html),
+            }
+        }
+        writer.write_footnotes(&mut html);
+        Ok(RenderedContent { html, sections })
+    }
+
+    /// Replaces every code block with its rendered `<pre>` element,
+    /// highlighting the ones marked as GDScript.
+    fn highlight_code_blocks(&mut self, node: &mut Node) -> inkjet::Result<()> {
+        if let Node::Code(code) = node {
+            let code_block = format!("{}\n", code.value);
+            let html = if code
+                .lang
+                .as_deref()
+                .is_some_and(|lang| lang.starts_with("GDScript"))
+            {
+                format!("<pre>{}</pre>", self.highlight_gdscript(&code_block)?)
+            } else {
+                format!("<pre>{}</pre>", escape(&code_block))
+            };
+            *node = Node::Html(Html {
+                value: html,
+                position: code.position.clone(),
+            });
+            return Ok(());
+        }
+        for child in node.children_mut().into_iter().flatten() {
+            self.highlight_code_blocks(child)?;
+        }
+        Ok(())
+    }
+
+    fn highlight_gdscript(&mut self, code: &str) -> inkjet::Result<String> {
+        let result = self.highlighter.highlight_to_string(
+            inkjet::Language::Gdscript,
+            &formatter::Html,
+            code,
+        )?;
+        // Doing crimes against Regex
+        let mut inside_tag = false;
+        let mut new_result = String::new();
+        for character in result.chars() {
+            match character {
+                '<' => inside_tag = true,
+                '>' => inside_tag = false,
+                '=' if !inside_tag => {
+                    new_result += "&equals;";
+                    continue;
+                }
+                _ => {}
+            };
+            new_result.push(character);
+        }
+        let result = self
+            .symbol_regex
+            .replace_all(new_result.as_str(), |captures: &Captures| {
+                format!("<span class='symbol'>{}</span>", &captures[0])
+            });
+        Ok(result.into_owned())
+    }
+}
+
+const DIRECTIVES: [&str; 3] = ["!insert ", "!image_subtitle ", "!html "];
+
+/// Surrounds directive lines with blank lines, so each one is parsed as its own paragraph.
+fn isolate_directives(markdown: &str) -> String {
+    let mut source = String::new();
+    let mut fence: Option<&str> = None;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        match fence {
+            Some(marker) if trimmed.starts_with(marker) => fence = None,
+            Some(_) => {}
+            None if trimmed.starts_with("```") => fence = Some("```"),
+            None if trimmed.starts_with("~~~") => fence = Some("~~~"),
+            None if DIRECTIVES
+                .iter()
+                .any(|directive| line.starts_with(directive)) =>
+            {
+                source += "\n";
+                source += line;
+                source += "\n\n";
+                continue;
+            }
+            None => {}
+        }
+        source += line;
+        source += "\n";
+    }
+    source
+}
+
+/// Turns raw HTML in the content into text, matching how single lines used to be rendered.
+/// `!html` is the way to insert HTML on purpose.
+fn escape_raw_html(node: &mut Node) {
+    if let Node::Html(html) = node {
+        *node = Node::Text(Text {
+            value: std::mem::take(&mut html.value),
+            position: html.position.take(),
+        });
+        return;
+    }
+    for child in node.children_mut().into_iter().flatten() {
+        escape_raw_html(child);
+    }
+}
+
+enum Directive {
+    Insert {
+        background: bool,
+        children: Vec<Node>,
+    },
+    ImageSubtitle(Vec<Node>),
+    Html(String),
+}
+
+/// Recognizes a top-level paragraph starting with one of the directives.
+fn parse_directive(node: &Node, source: &str) -> Option<Directive> {
+    let Node::Paragraph(paragraph) = node else {
+        return None;
+    };
+    let Some(Node::Text(first)) = paragraph.children.first() else {
+        return None;
+    };
+    let strip_prefix = |prefix: &str| -> Option<Vec<Node>> {
+        let rest = first.value.strip_prefix(prefix)?;
+        let mut children = paragraph.children.clone();
+        if rest.is_empty() {
+            children.remove(0);
+        } else if let Node::Text(text) = &mut children[0] {
+            text.value = rest.to_string();
+        }
+        Some(children)
+    };
+    if first.value.starts_with("!html ") {
+        let position = paragraph.position.as_ref()?;
+        let line = &source[position.start.offset..position.end.offset];
+        return Some(Directive::Html(line.replacen("!html ", "", 1)));
+    }
+    if let Some(children) = strip_prefix("!insert bg ") {
+        return Some(Directive::Insert {
+            background: true,
+            children,
+        });
+    }
+    if let Some(children) = strip_prefix("!insert ") {
+        return Some(Directive::Insert {
+            background: false,
+            children,
+        });
+    }
+    strip_prefix("!image_subtitle ").map(Directive::ImageSubtitle)
+}
+
+fn raw_html(value: &str) -> Node {
+    Node::Html(Html {
+        value: value.to_string(),
+        position: None,
+    })
+}
+
+/// Replaces directive paragraphs at the top level of the document with the HTML they stand for.
+/// An `!insert` holds the rest of its own line and is closed before the next block, unless that
+/// block is an `!image_subtitle`, which is added to the insert before closing it.
+fn apply_directives(children: Vec<Node>, source: &str) -> Vec<Node> {
+    let mut result: Vec<Node> = Vec::with_capacity(children.len());
+    let mut in_insert = false;
+    for node in children {
+        let directive = parse_directive(&node, source);
+        if in_insert {
+            in_insert = false;
+            if let Some(Directive::ImageSubtitle(subtitle)) = directive {
+                result.push(raw_html("<br><div class='insert-description'><em>"));
+                result.extend(subtitle);
+                result.push(raw_html(
+                    "</em></div></div></div></div><div class='post-text'>\n",
+                ));
+                continue;
+            }
+            result.push(raw_html("</div></div></div><div class='post-text'>\n"));
+        }
+        match directive {
+            Some(Directive::Insert {
+                background,
+                children,
+            }) => {
+                result.push(raw_html(if background {
+                    "</div><div class='post-insert with-background'><div class='insert-content'><div class='insert-content-inner'>"
+                } else {
+                    "</div><div class='post-insert'><div class='insert-content'><div class='insert-content-inner'>"
+                }));
+                result.extend(children);
+                in_insert = true;
+            }
+            Some(Directive::Html(html)) => result.push(raw_html(&html)),
+            _ => result.push(node),
+        }
+    }
+    if in_insert {
+        result.push(raw_html("</div></div></div><div class='post-text'>\n"));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INSERT: &str = "</div><div class='post-insert'><div class='insert-content'><div class='insert-content-inner'>";
+    const INSERT_END: &str = "</div></div></div><div class='post-text'>\n";
+
+    fn render(markdown: &str) -> String {
+        ContentRenderer::new()
+            .render(markdown, "test")
+            .unwrap()
+            .html
+    }
+
+    #[test]
+    fn insert_holds_its_line_and_closes_before_the_next_block() {
+        assert_eq!(
+            render("Before\n!insert ![alt](a.png)\nAfter\n"),
+            format!("<p>Before</p>\n{INSERT}<img src=\"a.png\" alt=\"alt\" />{INSERT_END}<p>After</p>\n")
+        );
+        assert_eq!(
+            render("!insert bg Wide"),
+            format!("</div><div class='post-insert with-background'><div class='insert-content'><div class='insert-content-inner'>Wide{INSERT_END}")
+        );
+    }
+
+    #[test]
+    fn image_subtitle_ends_the_insert_before_it() {
+        assert_eq!(
+            render("!insert ![alt](a.png)\n!image_subtitle A *subtitle*\nAfter\n"),
+            format!(
+                "{INSERT}<img src=\"a.png\" alt=\"alt\" /><br><div class='insert-description'><em>A <em>subtitle</em></em></div></div></div></div><div class='post-text'>\n<p>After</p>\n"
+            )
+        );
+    }
+
+    #[test]
+    fn html_directive_is_the_only_raw_html() {
+        assert_eq!(
+            render("!html <b>bold</b>\n<b>text</b>\n"),
+            "<b>bold</b><p>&lt;b&gt;text&lt;/b&gt;</p>\n"
+        );
+    }
+
+    #[test]
+    fn directives_in_code_blocks_stay_code() {
+        assert_eq!(
+            render("```\n!html <b>x</b>\n!insert x\n```\n"),
+            "<pre>!html &lt;b&gt;x&lt;/b&gt;\n!insert x\n</pre>"
+        );
+    }
+
+    #[test]
+    fn second_level_headings_are_sections() {
+        let rendered = ContentRenderer::new()
+            .render("## A *Section*\n\nText\n", "test")
+            .unwrap();
+        assert_eq!(rendered.sections.len(), 1);
+        assert_eq!(rendered.sections[0].id, "a_section");
+        assert_eq!(rendered.sections[0].title, "A <em>Section</em>");
+        assert!(rendered
+            .html
+            .contains("<h2 id='a_section'>A <em>Section</em>"));
+    }
+}
@@ -0,0 +1,206 @@
+//! Creates the directories of new posts and pages, ready to be filled in.
+
+use std::{fs, path::Path};
+
+use chrono::Utc;
+
+use crate::{
+    config::SiteConfig,
+    error::{IoResultExt, Result, ScrigenError},
+    site::parse_pages,
+    static_page::StaticPage,
+    tags::slug,
+};
+
+/// Directory name derived from a title, see [`slug`]. Titles with the same slug as an
+/// existing post or page, like "C#" and "C++", get a number appended: `c-2`, `c-3` and so on.
+fn directory_name(title: &str, taken: impl Fn(&str) -> bool) -> Result<String> {
+    let slug = slug(title);
+    if slug.is_empty() {
+        return Err(ScrigenError::EmptySlug {
+            title: title.to_string(),
+        });
+    }
+    Ok((1..)
+        .map(|number| match number {
+            1 => slug.clone(),
+            number => format!("{slug}-{number}"),
+        })
+        .find(|name| !taken(name))
+        .expect("there are more numbers than existing directories"))
+}
+
+/// Creates `entries/<slug>/` with a draft `meta.ron` dated today and an empty `content.md`,
+/// returning the new directory.
+pub fn new_post(config: &SiteConfig, title: &str) -> Result<String> {
+    let entries_directory = config.entries_directory();
+    let name = directory_name(title, |name| {
+        Path::new(&format!("{entries_directory}/{name}")).exists()
+    })?;
+    let directory = format!("{entries_directory}/{name}");
+    let today = Utc::now()
+        .with_timezone(&config.timezone)
+        .format("%Y-%m-%d")
+        .to_string();
+    let meta = format!(
+        "(
+    title: {},
+    summary: \"\",
+    author_name: {},
+    author_email: {},
+    author_uri: {},
+    image: \"\",
+    image_alt: \"\",
+    published: \"{today}\",
+    updated: \"{today}\",
+    tags: [],
+    draft: true,
+)
+",
+        quote(title),
+        quote(&config.author.name),
+        quote(&config.author.email),
+        quote(&config.author.uri),
+    );
+    create(&directory, &meta)?;
+    Ok(directory)
+}
+
+/// Creates `pages/<order>_<slug>/` after the last existing page, with a `meta.ron` and an
+/// empty `content.md`, returning the new directory.
+pub fn new_page(config: &SiteConfig, name: &str) -> Result<String> {
+    let pages_directory = config.pages_directory();
+    let pages = top_level_pages(config)?;
+    let order = pages
+        .iter()
+        .filter_map(|page| page.order)
+        .map(|order| order.saturating_add(1))
+        .max()
+        .unwrap_or_default();
+    let id = directory_name(name, |id| pages.iter().any(|page| page.id == id))?;
+    let directory = format!("{pages_directory}/{order}_{id}");
+    let meta = format!(
+        "(
+    name: {},
+    title: {},
+    summary: \"\",
+)
+",
+        quote(name),
+        quote(name),
+    );
+    create(&directory, &meta)?;
+    Ok(directory)
+}
+
+/// The existing top-level pages, with their order taken from their `meta.ron` or directory
+/// name like in a build.
+fn top_level_pages(config: &SiteConfig) -> Result<Vec<StaticPage>> {
+    let pages_directory = config.pages_directory();
+    if !fs::exists(&pages_directory).with_path(&pages_directory)? {
+        return Ok(Vec::new());
+    }
+    let mut errors = Vec::new();
+    let pages = parse_pages(config, &mut errors);
+    if !errors.is_empty() {
+        return Err(ScrigenError::Content(errors));
+    }
+    Ok(pages
+        .into_iter()
+        .filter(|page| page.parent.is_none())
+        .collect())
+}
+
+fn create(directory: &str, meta: &str) -> Result<()> {
+    if let Some((parent, _)) = directory.rsplit_once('/') {
+        fs::create_dir_all(parent).with_path(parent)?;
+    }
+    // Fails if the directory exists, so nothing is ever overwritten.
+    fs::create_dir(directory).with_path(directory)?;
+    let meta_path = format!("{directory}/meta.ron");
+    fs::write(&meta_path, meta).with_path(&meta_path)?;
+    let content_path = format!("{directory}/content.md");
+    fs::write(&content_path, "").with_path(&content_path)
+}
+
+/// A RON string literal.
+fn quote(value: &str) -> String {
+    ron::to_string(value).expect("strings can always be serialized")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Config of a site in a fresh temporary directory with the given pages.
+    fn site(name: &str, pages: &[(&str, &str)]) -> SiteConfig {
+        let source =
+            std::env::temp_dir().join(format!("scrigen-scaffold-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&source);
+        for (directory, meta) in pages {
+            fs::create_dir_all(source.join(directory)).unwrap();
+            fs::write(source.join(directory).join("meta.ron"), meta).unwrap();
+            fs::write(source.join(directory).join("content.md"), "").unwrap();
+        }
+        fs::create_dir_all(&source).unwrap();
+        fs::write(
+            source.join("site.ron"),
+            r#"(
+    title: "Test",
+    base_url: "https://example.org",
+    author: (name: "Author", email: "author@example.org", uri: "https://example.org"),
+)"#,
+        )
+        .unwrap();
+        SiteConfig::load(&source.to_string_lossy()).unwrap()
+    }
+
+    #[test]
+    fn new_pages_are_ordered_after_prefixes_and_meta_orders() {
+        let config = site(
+            "order",
+            &[
+                (
+                    "pages/3_projects",
+                    r#"(name: "Projects", title: "Projects", summary: "")"#,
+                ),
+                (
+                    "pages/about",
+                    r#"(name: "About", title: "About", summary: "", order: 5)"#,
+                ),
+            ],
+        );
+
+        let directory = new_page(&config, "Contact").unwrap();
+
+        assert!(directory.ends_with("/pages/6_contact"));
+        fs::remove_dir_all(&config.source_directory).unwrap();
+    }
+
+    #[test]
+    fn new_pages_get_a_free_id() {
+        let config = site(
+            "page-id",
+            &[("pages/1_c", r#"(name: "C#", title: "C#", summary: "")"#)],
+        );
+
+        let directory = new_page(&config, "C++").unwrap();
+
+        assert!(directory.ends_with("/pages/2_c-2"));
+        fs::remove_dir_all(&config.source_directory).unwrap();
+    }
+
+    #[test]
+    fn new_posts_get_a_free_directory() {
+        let config = site("post", &[]);
+
+        let first = new_post(&config, "C#").unwrap();
+        let second = new_post(&config, "C++").unwrap();
+        let words = new_post(&config, "Using GDScript in Godot 4").unwrap();
+
+        assert!(first.ends_with("/entries/c"));
+        assert!(second.ends_with("/entries/c-2"));
+        assert!(words.ends_with("/entries/using-gdscript-in-godot-4"));
+        fs::remove_dir_all(&config.source_directory).unwrap();
+    }
+}
@@ -0,0 +1,247 @@
+//! Local preview of a site: builds it into a temporary directory, serves it over HTTP and
+//! rebuilds it whenever its sources change, reloading open browser tabs. Files the build
+//! doesn't generate, like stylesheets and fonts, can be served from the site's usual target
+//! directory.
+
+use std::{
+    fs,
+    path::{Component, Path},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::Duration,
+};
+
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::{
+    error::{Result, ScrigenError},
+    site::{BuildOptions, Site},
+    watch::watch,
+};
+
+/// Path browsers poll to learn about rebuilds.
+const RELOAD_PATH: &str = "/__scrigen/reload";
+
+/// How long a reload request waits for a rebuild before the browser asks again.
+const RELOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Injected into every HTML page, reloads it once the site has been rebuilt.
+const RELOAD_SCRIPT: &str = "<script>
+(function poll(version) {
+	fetch('/__scrigen/reload?version=' + version)
+		.then(response => response.text())
+		.then(current => current === String(version) ? poll(version) : location.reload())
+		.catch(() => setTimeout(() => poll(version), 1000));
+})(VERSION);
+</script>
+";
+
+/// Number of completed builds, waited on by the reload requests of open pages.
+#[derive(Default)]
+struct Builds {
+    version: Mutex<u64>,
+    finished: Condvar,
+}
+
+impl Builds {
+    fn version(&self) -> u64 {
+        *self
+            .version
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    fn finish(&self) {
+        *self
+            .version
+            .lock()
+            .unwrap_or_else(|error| error.into_inner()) += 1;
+        self.finished.notify_all();
+    }
+
+    /// Blocks until a build newer than `version` finished or the timeout passed.
+    fn wait_newer(&self, version: u64) -> u64 {
+        let guard = self
+            .version
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        let (guard, _) = self
+            .finished
+            .wait_timeout_while(guard, RELOAD_TIMEOUT, |current| *current == version)
+            .unwrap_or_else(|error| error.into_inner());
+        *guard
+    }
+}
+
+/// Serves the site at `http://127.0.0.1:<port>/` until the process is stopped. Paths the
+/// preview doesn't have are looked up in `fallback_directory`, if given.
+pub fn serve(
+    source_directory: &str,
+    fallback_directory: Option<&str>,
+    options: &BuildOptions,
+    port: u16,
+) -> Result<()> {
+    let target_directory = std::env::temp_dir()
+        .join(format!("scrigen-serve-{port}"))
+        .to_string_lossy()
+        .to_string();
+    let builds = Arc::new(Builds::default());
+    rebuild(source_directory, &target_directory, options, &builds);
+
+    let address = format!("127.0.0.1:{port}");
+    let server = Server::http(&address).map_err(|source| ScrigenError::Serve {
+        address: address.clone(),
+        source,
+    })?;
+    println!("Serving {source_directory} at http://{address}/");
+    {
+        let mut directories = vec![target_directory.clone()];
+        directories.extend(fallback_directory.map(str::to_string));
+        let (directories, builds) = (Arc::new(directories), builds.clone());
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let (directories, builds) = (directories.clone(), builds.clone());
+                thread::spawn(move || respond(request, &directories, &builds));
+            }
+        });
+    }
+
+    watch(source_directory, |_| {
+        rebuild(source_directory, &target_directory, options, &builds)
+    })
+}
+
+/// Builds the site, keeping the previous build if the content has errors. The build cache
+/// skips unchanged outputs and the build removes the ones that are gone.
+fn rebuild(
+    source_directory: &str,
+    target_directory: &str,
+    options: &BuildOptions,
+    builds: &Builds,
+) {
+    let result = Site::load_with_options(source_directory, options)
+        .and_then(|site| site.build(target_directory));
+    match result {
+        Ok(()) => {
+            builds.finish();
+            println!("Built the site");
+        }
+        Err(error) => {
+            for error in error.into_errors() {
+                eprintln!("error: {error}");
+            }
+        }
+    }
+}
+
+/// Answers a request with the file from the first of `directories` that has it.
+fn respond(request: Request, directories: &[String], builds: &Builds) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let response = if path == RELOAD_PATH {
+        let version = query
+            .strip_prefix("version=")
+            .and_then(|version| version.parse().ok())
+            .unwrap_or_else(|| builds.version());
+        text_response(200, &builds.wait_newer(version).to_string())
+    } else {
+        let file_paths: Vec<String> = directories
+            .iter()
+            .filter_map(|directory| file_path(directory, path))
+            .collect();
+        match file_paths
+            .iter()
+            .find(|file_path| Path::new(file_path).is_file())
+        {
+            Some(file_path) => file_response(file_path, builds.version()),
+            None => text_response(404, "Not found"),
+        }
+    };
+    // The browser may have gone away in the meantime, there is nobody left to tell.
+    let _ = request.respond(response);
+}
+
+/// Maps a URL path to a file in the built site, serving `index.html` for directories.
+fn file_path(target_directory: &str, url_path: &str) -> Option<String> {
+    let relative = percent_decode(url_path.trim_start_matches('/'))?;
+    if Path::new(&relative)
+        .components()
+        .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+    let path = format!("{target_directory}/{relative}");
+    if Path::new(&path).is_dir() {
+        Some(format!("{}/index.html", path.trim_end_matches('/')))
+    } else {
+        Some(path)
+    }
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn file_response(path: &str, version: u64) -> Response<std::io::Cursor<Vec<u8>>> {
+    let Ok(mut data) = fs::read(path) else {
+        return text_response(404, "Not found");
+    };
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or("");
+    if extension == "html" {
+        let script = RELOAD_SCRIPT.replace("VERSION", &version.to_string());
+        let html = String::from_utf8_lossy(&data);
+        data = match html.rfind("</body>") {
+            Some(end) => format!("{}{script}{}", &html[..end], &html[end..]),
+            None => format!("{html}{script}"),
+        }
+        .into_bytes();
+    }
+    Response::from_data(data).with_header(content_type(content_type_of(extension)))
+}
+
+fn text_response(status: u16, text: &str) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(text)
+        .with_status_code(status)
+        .with_header(content_type("text/plain; charset=utf-8"))
+}
+
+fn content_type(mime_type: &str) -> Header {
+    Header::from_bytes("Content-Type", mime_type).expect("MIME types are valid header values")
+}
+
+fn content_type_of(extension: &str) -> &'static str {
+    match extension {
+        "html" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "xml" => "application/atom+xml",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,817 @@
+use atom_syndication::{Entry, Feed, Generator, Link, Person, Text};
+use chrono::Utc;
+use minijinja::{context, Value};
+use rayon::prelude::*;
+use std::{cmp::Ordering, collections::HashMap, fmt, fs, path::Path, sync::OnceLock};
+
+use crate::{
+    archive::{collect_archive, Year},
+    assets::copy_assets,
+    blog_post::BlogPost,
+    cache::{write_if_changed, Cache},
+    config::SiteConfig,
+    date::Timestamp,
+    error::{IoResultExt, Result, ScrigenError},
+    menu::Menu,
+    neighbours::PostNeighbours,
+    page::Page,
+    static_page::{split_order, StaticPage},
+    tags::{collect_tags, same_tag, slug, Tag},
+    templates::Templates,
+};
+
+/// A single step of building the site.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Step {
+    Posts,
+    Pages,
+    Landing,
+    Feed,
+    Tags,
+    Archive,
+}
+
+impl Step {
+    /// Every step, in the order a full build runs them.
+    pub const ALL: [Step; 6] = [
+        Step::Posts,
+        Step::Landing,
+        Step::Feed,
+        Step::Tags,
+        Step::Archive,
+        Step::Pages,
+    ];
+
+    /// The templates a step renders its pages with, not counting the ones they include.
+    pub fn templates(self, config: &SiteConfig) -> &'static [&'static str] {
+        match self {
+            // Feed entries contain the complete post.
+            Step::Posts | Step::Feed => &["post.html"],
+            Step::Pages => &["page.html"],
+            Step::Landing => &["landing.html"],
+            // So do the entries of the tag feeds.
+            Step::Tags if config.features.feed => &["tag.html", "tags.html", "post.html"],
+            Step::Tags => &["tag.html", "tags.html"],
+            Step::Archive => &["archive.html"],
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Step::Posts => "posts",
+            Step::Pages => "pages",
+            Step::Landing => "landing page",
+            Step::Feed => "feed",
+            Step::Tags => "tags",
+            Step::Archive => "archive",
+        })
+    }
+}
+
+/// Which posts make it into a build.
+#[derive(Clone, Default, Debug)]
+pub struct BuildOptions {
+    /// Include posts marked as `draft: true`.
+    pub drafts: bool,
+    /// Include posts published after `now`.
+    pub future: bool,
+    /// Cut-off for scheduled posts, defaults to the current time.
+    pub now: Option<Timestamp>,
+}
+
+impl BuildOptions {
+    /// Whether a post belongs in a build with these options.
+    pub fn includes(&self, post: &BlogPost, config: &SiteConfig) -> bool {
+        let now = match self.now {
+            Some(now) => now.in_timezone(config.timezone),
+            None => Utc::now().fixed_offset(),
+        };
+        (self.drafts || !post.draft) && (self.future || post.published() <= now)
+    }
+}
+
+/// Names of tags by their slug.
+type TagNames = HashMap<String, String>;
+
+/// A loaded site: its configuration and all parsed posts and pages.
+pub struct Site {
+    config: SiteConfig,
+    templates: Templates,
+    blog_posts: Vec<BlogPost>,
+    static_pages: Vec<StaticPage>,
+    menu: Menu,
+    /// Names of the tags of every post by slug, including the posts left out of this build.
+    tag_slugs: TagNames,
+    /// Complete HTML of each post by id, rendered on first use.
+    rendered_posts: HashMap<String, OnceLock<String>>,
+}
+
+impl Site {
+    /// Reads `site.ron` and all content from a source directory, collecting every content error.
+    pub fn load(source_directory: &str) -> Result<Self> {
+        Self::load_with_options(source_directory, &BuildOptions::default())
+    }
+
+    /// Like [`Site::load`], but decides which posts are included with the given options.
+    pub fn load_with_options(source_directory: &str, options: &BuildOptions) -> Result<Self> {
+        let config = SiteConfig::load(source_directory)?;
+        let (static_pages, blog_posts, tag_slugs) = parse_content(&config, options)?;
+        let mut site = Self {
+            templates: Templates::load(&config),
+            config,
+            rendered_posts: blog_posts
+                .iter()
+                .map(|post| (post.id.clone(), OnceLock::new()))
+                .collect(),
+            blog_posts,
+            static_pages,
+            menu: Menu::default(),
+            tag_slugs,
+        };
+        // The default menu is made from the parsed pages.
+        site.menu = match &site.config.menu {
+            Some(menu) => menu.clone(),
+            None => Menu::from_pages(&site),
+        };
+        let errors = site.menu.check(&site);
+        if !errors.is_empty() {
+            return Err(ScrigenError::Content(errors));
+        }
+        Ok(site)
+    }
+
+    pub fn config(&self) -> &SiteConfig {
+        &self.config
+    }
+
+    pub fn templates(&self) -> &Templates {
+        &self.templates
+    }
+
+    /// All posts, newest first.
+    pub fn posts(&self) -> &[BlogPost] {
+        &self.blog_posts
+    }
+
+    /// The posts `post` links to at its end.
+    pub fn neighbours(&self, post: &BlogPost) -> PostNeighbours<'_> {
+        PostNeighbours::new(&self.blog_posts, post, &self.config)
+    }
+
+    /// All static pages in navigation order, each followed by the pages nested in it.
+    pub fn pages(&self) -> &[StaticPage] {
+        &self.static_pages
+    }
+
+    /// The pages `page` is nested in, outermost first.
+    pub fn ancestors(&self, page: &StaticPage) -> Vec<&StaticPage> {
+        let mut ancestors = Vec::new();
+        let mut parent = page.parent.as_deref();
+        while let Some(id) = parent {
+            let Some(page) = self.static_pages.iter().find(|page| page.id == id) else {
+                break;
+            };
+            ancestors.insert(0, page);
+            parent = page.parent.as_deref();
+        }
+        ancestors
+    }
+
+    /// The pages directly below `parent`, or the top-level pages, as seen by templates. Each
+    /// has its own `children`.
+    pub fn page_tree(&self, parent: Option<&str>) -> Vec<Value> {
+        self.static_pages
+            .iter()
+            .filter(|page| page.parent.as_deref() == parent)
+            .map(|page| {
+                context! {
+                    children => self.page_tree(Some(&page.id)),
+                    ..page.context(&self.config)
+                }
+            })
+            .collect()
+    }
+
+    /// All posts grouped by year and month, newest first.
+    pub fn archive(&self) -> Vec<Year<'_>> {
+        collect_archive(&self.blog_posts)
+    }
+
+    /// The navigation menu as seen by templates on the page written to `current`, see
+    /// [`Menu::context`].
+    pub fn menu(&self, current: &str) -> Vec<Value> {
+        self.menu.context(self, current)
+    }
+
+    /// All tags used by posts, sorted by name.
+    pub fn tags(&self) -> Vec<Tag<'_>> {
+        collect_tags(&self.blog_posts)
+    }
+
+    /// Whether any post has a tag, even if only drafts or scheduled posts left out of this
+    /// build have it.
+    pub fn has_tag(&self, name: &str) -> bool {
+        self.tag_slugs.contains_key(&slug(name))
+    }
+
+    /// Renders the complete HTML document of a post. Each post of the site is only rendered
+    /// once, its page and its feed entries share the result.
+    pub fn render_post(&self, post: &BlogPost) -> Result<String> {
+        let Some(rendered) = self.rendered_posts.get(&post.id) else {
+            return post.to_html(self);
+        };
+        if let Some(html) = rendered.get() {
+            return Ok(html.clone());
+        }
+        let html = post.to_html(self)?;
+        Ok(rendered.get_or_init(|| html).clone())
+    }
+
+    pub fn render_page(&self, page: &StaticPage) -> Result<String> {
+        page.to_html(self)
+    }
+
+    /// Number of pages the landing page's list of posts is split into.
+    pub fn landing_page_count(&self) -> usize {
+        match self.config.posts_per_page {
+            Some(per_page) => self.blog_posts.len().div_ceil(per_page.get()).max(1),
+            None => 1,
+        }
+    }
+
+    /// Renders a page of the landing page, counting from 1 up to
+    /// [`Site::landing_page_count`].
+    pub fn render_landing_page(&self, number: usize) -> Result<String> {
+        let config = &self.config;
+        let count = self.landing_page_count();
+        if !(1..=count).contains(&number) {
+            return Err(ScrigenError::NoLandingPage { number, count });
+        }
+        let posts = match config.posts_per_page {
+            // Without posts, the only page is empty.
+            Some(per_page) => self
+                .blog_posts
+                .chunks(per_page.get())
+                .nth(number - 1)
+                .unwrap_or_default(),
+            None => &self.blog_posts,
+        };
+        let directory = landing_directory(config, number);
+        let root = root_of(&directory);
+        let href = |number| format!("{root}{}index.html", landing_directory(config, number));
+        self.templates.render(
+            "landing.html",
+            context! {
+                site => config,
+                posts => posts.iter().map(|post| post.context(config)).collect::<Vec<_>>(),
+                pagination => context! {
+                    current => number,
+                    count => count,
+                    previous => (number > 1).then(|| href(number - 1)),
+                    next => (number < count).then(|| href(number + 1)),
+                    pages => (1..=count)
+                        .map(|page| context! { number => page, href => href(page), current => page == number })
+                        .collect::<Vec<_>>(),
+                },
+                pages => self.page_tree(None),
+                menu => self.menu(&directory),
+                root => root,
+            },
+        )
+    }
+
+    pub fn render_tag_page(&self, tag: &Tag) -> Result<String> {
+        let config = &self.config;
+        let directory = format!(
+            "{}/{}/{}/",
+            config.output.blog, config.output.tags, tag.slug
+        );
+        self.templates.render(
+            "tag.html",
+            context! {
+                site => config,
+                tag => context! { name => tag.name, slug => tag.slug },
+                posts => tag.posts.iter().map(|post| post.context(config)).collect::<Vec<_>>(),
+                pages => self.page_tree(None),
+                menu => self.menu(&directory),
+                root => root_of(&directory),
+            },
+        )
+    }
+
+    /// Renders the overview of all tags and how many posts carry each of them.
+    pub fn render_tags_page(&self) -> Result<String> {
+        let config = &self.config;
+        let directory = format!("{}/{}/", config.output.blog, config.output.tags);
+        self.templates.render(
+            "tags.html",
+            context! {
+                site => config,
+                tags => self
+                    .tags()
+                    .iter()
+                    .map(|tag| context! { name => tag.name, slug => tag.slug, count => tag.posts.len() })
+                    .collect::<Vec<_>>(),
+                pages => self.page_tree(None),
+                menu => self.menu(&directory),
+                root => root_of(&directory),
+            },
+        )
+    }
+
+    /// Renders the archive of all posts.
+    pub fn render_archive_page(&self) -> Result<String> {
+        let config = &self.config;
+        let directory = format!("{}/{}/", config.output.blog, config.output.archive);
+        self.templates.render(
+            "archive.html",
+            context! {
+                site => config,
+                years => self.archive().iter().map(|year| year.context(config)).collect::<Vec<_>>(),
+                pages => self.page_tree(None),
+                menu => self.menu(&directory),
+                root => root_of(&directory),
+            },
+        )
+    }
+
+    /// Renders the archive of the posts published in one year.
+    pub fn render_year_page(&self, year: &Year) -> Result<String> {
+        let config = &self.config;
+        let directory = format!("{}/{}/", config.output.blog, year.year);
+        self.templates.render(
+            "archive.html",
+            context! {
+                site => config,
+                year => year.year,
+                years => vec![year.context(config)],
+                pages => self.page_tree(None),
+                menu => self.menu(&directory),
+                root => root_of(&directory),
+            },
+        )
+    }
+
+    /// Runs every build step enabled in the site's configuration. Outputs whose inputs didn't
+    /// change since the last build into the same target directory are skipped, outputs of
+    /// earlier builds that this one doesn't produce anymore are deleted.
+    pub fn build(&self, target_directory: &str) -> Result<()> {
+        let mut cache = self.load_cache(target_directory)?;
+        for step in Step::ALL {
+            self.run_step(step, target_directory, &mut cache)?;
+        }
+        cache.remove_stale()?;
+        cache.save()
+    }
+
+    /// Writes a single post and copies its assets.
+    pub fn build_post(&self, post: &BlogPost, target_directory: &str) -> Result<()> {
+        let mut cache = self.load_cache(target_directory)?;
+        if !cache.is_fresh(
+            &post_output(&self.config, post),
+            cache.post_key(self, post)?,
+        ) {
+            write_post(self, post, target_directory)?;
+        }
+        cache.save()
+    }
+
+    /// Writes a single static page and copies its assets.
+    pub fn build_page(&self, page: &StaticPage, target_directory: &str) -> Result<()> {
+        if !self.config.features.pages {
+            return Ok(());
+        }
+        let mut cache = self.load_cache(target_directory)?;
+        if !cache.is_fresh(&page_output(page), cache.page_key(page)?) {
+            write_static_page(self, page, target_directory)?;
+        }
+        cache.save()
+    }
+
+    /// Runs a single build step, unless it is turned off in the site's configuration.
+    pub fn build_step(&self, step: Step, target_directory: &str) -> Result<()> {
+        let mut cache = self.load_cache(target_directory)?;
+        self.run_step(step, target_directory, &mut cache)?;
+        cache.save()
+    }
+
+    fn load_cache(&self, target_directory: &str) -> Result<Cache> {
+        let blog_directory = format!("{target_directory}/{}", self.config.output.blog);
+        fs::create_dir_all(&blog_directory).with_path(&blog_directory)?;
+        Cache::load(self, target_directory)
+    }
+
+    fn run_step(&self, step: Step, target_directory: &str, cache: &mut Cache) -> Result<()> {
+        let config = &self.config;
+        match step {
+            Step::Posts => write_posts(self, target_directory, cache),
+            Step::Landing if config.features.landing => {
+                write_landing_page(self, target_directory, cache)
+            }
+            Step::Feed if config.features.feed => write_feed(self, target_directory, cache),
+            Step::Pages if config.features.pages => {
+                write_static_pages(self, target_directory, cache)
+            }
+            Step::Tags if config.features.tags => write_tag_pages(self, target_directory, cache),
+            Step::Archive if config.features.archive => {
+                write_archive(self, target_directory, cache)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Parses all pages and posts, collecting every error instead of stopping at the first one.
+/// Also returns the names of the tags of every post by slug, including the posts left out.
+fn parse_content(
+    config: &SiteConfig,
+    options: &BuildOptions,
+) -> Result<(Vec<StaticPage>, Vec<BlogPost>, TagNames)> {
+    let mut errors: Vec<ScrigenError> = Vec::new();
+    let static_pages = if config.features.pages {
+        parse_pages(config, &mut errors)
+    } else {
+        Vec::new()
+    };
+    let (blog_posts, tag_slugs) = parse_posts(config, options, &mut errors);
+    if !errors.is_empty() {
+        return Err(ScrigenError::Content(errors));
+    }
+    Ok((static_pages, blog_posts, tag_slugs))
+}
+
+/// Lists the non-hidden entries of a content directory, sorted by name.
+fn content_directories(path: &str, errors: &mut Vec<ScrigenError>) -> Vec<(String, String)> {
+    let entries = match fs::read_dir(path).with_path(path) {
+        Ok(entries) => entries,
+        Err(error) => {
+            errors.push(error);
+            return Vec::new();
+        }
+    };
+    let mut directories: Vec<(String, String)> = Vec::new();
+    for entry in entries {
+        match entry.with_path(path) {
+            Ok(entry) => {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with(".") {
+                    directories.push((name, entry.path().to_string_lossy().to_string()));
+                }
+            }
+            Err(error) => errors.push(error),
+        }
+    }
+    directories.sort();
+    directories
+}
+
+/// Parses every page, each followed by the pages nested in it, in navigation order.
+pub(crate) fn parse_pages(config: &SiteConfig, errors: &mut Vec<ScrigenError>) -> Vec<StaticPage> {
+    let mut pages: Vec<StaticPage> = Vec::new();
+    parse_page_directory(config, &config.pages_directory(), None, &mut pages, errors);
+    pages
+}
+
+/// Parses the pages in `directory` and, depth first, the pages nested in them. Every entry of
+/// the pages directory is a page, below that only subdirectories with a `meta.ron` are.
+fn parse_page_directory(
+    config: &SiteConfig,
+    directory: &str,
+    parent: Option<&str>,
+    pages: &mut Vec<StaticPage>,
+    errors: &mut Vec<ScrigenError>,
+) {
+    let mut siblings: Vec<StaticPage> = Vec::new();
+    for (path_name, page_path) in content_directories(directory, errors) {
+        if parent.is_some() && !Path::new(&page_path).join("meta.ron").is_file() {
+            continue;
+        }
+        let (prefix, name) = split_order(&path_name);
+        match StaticPage::from_path(page_path.clone(), config) {
+            Ok(mut page) => {
+                let Some(order) = page.order.or(prefix).filter(|_| !name.is_empty()) else {
+                    errors.push(ScrigenError::InvalidPageDirectory { path: page_path });
+                    continue;
+                };
+                page.order = Some(order);
+                page.id = match parent {
+                    Some(parent) => format!("{parent}/{name}"),
+                    None => name.to_string(),
+                };
+                page.parent = parent.map(str::to_string);
+                siblings.push(page);
+            }
+            Err(error) => errors.extend(error.into_errors()),
+        }
+    }
+    // Pages of the same order stay sorted by directory name.
+    siblings.sort_by_key(|page| page.order);
+    for page in siblings {
+        let (id, source_path) = (page.id.clone(), page.source_path.clone());
+        pages.push(page);
+        parse_page_directory(config, &source_path, Some(&id), pages, errors);
+    }
+}
+
+fn parse_posts(
+    config: &SiteConfig,
+    options: &BuildOptions,
+    errors: &mut Vec<ScrigenError>,
+) -> (Vec<BlogPost>, TagNames) {
+    let mut blog_posts: Vec<BlogPost> = Vec::new();
+    let mut tag_slugs = TagNames::new();
+    for (id, post_path) in content_directories(&config.entries_directory(), errors) {
+        match BlogPost::from_path(post_path, config) {
+            Ok(mut post) => {
+                for tag in &post.tags {
+                    let tag_slug = slug(tag);
+                    match tag_slugs.get(&tag_slug) {
+                        // Tags without a slug are reported by the post.
+                        _ if tag_slug.is_empty() => {}
+                        Some(other) if !same_tag(tag, other) => {
+                            errors.push(ScrigenError::TagSlugClash {
+                                path: format!("{}/meta.ron", post.source_path),
+                                tag: tag.clone(),
+                                other: other.clone(),
+                                slug: tag_slug,
+                            })
+                        }
+                        Some(_) => {}
+                        None => {
+                            tag_slugs.insert(tag_slug, tag.clone());
+                        }
+                    }
+                }
+                if options.includes(&post, config) {
+                    post.id = id;
+                    blog_posts.push(post);
+                }
+            }
+            Err(error) => errors.extend(error.into_errors()),
+        }
+    }
+    // Posts published at the same time are ordered by id, so builds don't depend on the
+    // order the file system lists them in.
+    blog_posts.sort_by(|entry1, entry2| -> Ordering {
+        entry2
+            .published()
+            .cmp(&entry1.published())
+            .then_with(|| entry1.id.cmp(&entry2.id))
+    });
+    (blog_posts, tag_slugs)
+}
+
+fn post_output(config: &SiteConfig, post: &BlogPost) -> String {
+    format!("{}/{}/index.html", config.output.blog, post.id)
+}
+
+/// Renders and writes all posts that changed since the last build, in parallel.
+fn write_posts(site: &Site, target_directory: &str, cache: &mut Cache) -> Result<()> {
+    let mut changed_posts: Vec<&BlogPost> = Vec::new();
+    for post in site.posts() {
+        if !cache.is_fresh(
+            &post_output(site.config(), post),
+            cache.post_key(site, post)?,
+        ) {
+            changed_posts.push(post);
+        }
+    }
+    changed_posts
+        .into_par_iter()
+        .try_for_each(|post| write_post(site, post, target_directory))
+}
+
+fn write_post(site: &Site, post: &BlogPost, target_directory: &str) -> Result<()> {
+    let config = site.config();
+    let output = post_output(config, post);
+    let html = site.render_post(post)?;
+    let directory = format!("{target_directory}/{}/{}", config.output.blog, post.id);
+    fs::create_dir_all(&directory).with_path(&directory)?;
+    write_if_changed(&format!("{target_directory}/{output}"), html.as_bytes())?;
+    copy_assets(config, &post.source_path, &directory)
+}
+
+fn page_output(page: &StaticPage) -> String {
+    format!("{}/index.html", page.id)
+}
+
+/// Renders and writes all pages that changed since the last build, in parallel.
+fn write_static_pages(site: &Site, target_directory: &str, cache: &mut Cache) -> Result<()> {
+    let mut changed_pages: Vec<&StaticPage> = Vec::new();
+    for page in site.pages() {
+        if !cache.is_fresh(&page_output(page), cache.page_key(page)?) {
+            changed_pages.push(page);
+        }
+    }
+    changed_pages
+        .into_par_iter()
+        .try_for_each(|page| write_static_page(site, page, target_directory))
+}
+
+fn write_static_page(site: &Site, page: &StaticPage, target_directory: &str) -> Result<()> {
+    let output = page_output(page);
+    let html = site.render_page(page)?;
+    let directory = format!("{target_directory}/{}", page.id);
+    fs::create_dir_all(&directory).with_path(&directory)?;
+    write_if_changed(&format!("{target_directory}/{output}"), html.as_bytes())?;
+    copy_assets(site.config(), &page.source_path, &directory)
+}
+
+/// Relative path back to the site root from a directory of the site, given relative to the
+/// root with a trailing slash, e.g. `../../` for `blog/first-post/`.
+pub(crate) fn root_of(directory: &str) -> String {
+    "../".repeat(directory.matches('/').count())
+}
+
+/// Directory a page of the landing page is written to, relative to the site root with a
+/// trailing slash.
+fn landing_directory(config: &SiteConfig, number: usize) -> String {
+    match number {
+        1 => String::new(),
+        number => format!("{}/{number}/", config.output.pagination),
+    }
+}
+
+/// Writes every page of the landing page and removes pages left over from builds with more
+/// posts.
+fn write_landing_page(site: &Site, target_directory: &str, cache: &mut Cache) -> Result<()> {
+    let config = site.config();
+    let count = site.landing_page_count();
+    let mut fresh = true;
+    for number in 1..=count {
+        let output = format!("{}index.html", landing_directory(config, number));
+        fresh &= cache.is_fresh(&output, cache.posts_key());
+    }
+    if !fresh {
+        for number in 1..=count {
+            let directory = format!("{target_directory}/{}", landing_directory(config, number));
+            fs::create_dir_all(&directory).with_path(&directory)?;
+            let html = site.render_landing_page(number)?;
+            write_if_changed(&format!("{directory}index.html"), html.as_bytes())?;
+        }
+    }
+    for number in count + 1.. {
+        let directory = format!("{target_directory}/{}", landing_directory(config, number));
+        if !Path::new(&directory).exists() {
+            break;
+        }
+        fs::remove_dir_all(&directory).with_path(&directory)?;
+    }
+    Ok(())
+}
+
+fn write_tag_pages(site: &Site, target_directory: &str, cache: &mut Cache) -> Result<()> {
+    let config = site.config();
+    let output = format!("{}/{}/index.html", config.output.blog, config.output.tags);
+    let tags = site.tags();
+    // Every output is recorded, so the ones of tags without posts can be removed.
+    let mut fresh = cache.is_fresh(&output, cache.posts_key());
+    for tag in &tags {
+        let directory = format!("{}/{}/{}", config.output.blog, config.output.tags, tag.slug);
+        fresh &= cache.is_fresh(&format!("{directory}/index.html"), cache.posts_key());
+        if config.features.feed {
+            let feed = format!("{directory}/{}", config.output.feed);
+            fresh &= cache.is_fresh(&feed, cache.posts_key());
+        }
+    }
+    if fresh {
+        return Ok(());
+    }
+    let tags_directory = format!(
+        "{target_directory}/{}/{}",
+        config.output.blog, config.output.tags
+    );
+    for tag in tags {
+        let html = site.render_tag_page(&tag)?;
+        let directory = format!("{tags_directory}/{}", tag.slug);
+        fs::create_dir_all(&directory).with_path(&directory)?;
+        write_if_changed(&format!("{directory}/index.html"), html.as_bytes())?;
+        if config.features.feed {
+            write_tag_feed(site, &tag, &directory)?;
+        }
+    }
+    let html = site.render_tags_page()?;
+    fs::create_dir_all(&tags_directory).with_path(&tags_directory)?;
+    write_if_changed(&format!("{target_directory}/{output}"), html.as_bytes())
+}
+
+/// Writes the archive and a page for every year.
+fn write_archive(site: &Site, target_directory: &str, cache: &mut Cache) -> Result<()> {
+    let config = site.config();
+    let output = format!(
+        "{}/{}/index.html",
+        config.output.blog, config.output.archive
+    );
+    let archive = site.archive();
+    let mut fresh = cache.is_fresh(&output, cache.posts_key());
+    for year in &archive {
+        let year_output = format!("{}/{}/index.html", config.output.blog, year.year);
+        fresh &= cache.is_fresh(&year_output, cache.posts_key());
+    }
+    if fresh {
+        return Ok(());
+    }
+    for year in archive {
+        let html = site.render_year_page(&year)?;
+        let directory = format!("{target_directory}/{}/{}", config.output.blog, year.year);
+        fs::create_dir_all(&directory).with_path(&directory)?;
+        write_if_changed(&format!("{directory}/index.html"), html.as_bytes())?;
+    }
+    let html = site.render_archive_page()?;
+    let directory = format!(
+        "{target_directory}/{}/{}",
+        config.output.blog, config.output.archive
+    );
+    fs::create_dir_all(&directory).with_path(&directory)?;
+    write_if_changed(&format!("{target_directory}/{output}"), html.as_bytes())
+}
+
+/// Feed-level metadata shared by the site's feed and the per-tag feeds.
+struct FeedInfo {
+    title: String,
+    id: String,
+    /// URL of the HTML page the feed belongs to.
+    alternate_url: String,
+    /// URL the feed itself is published at.
+    self_url: String,
+}
+
+fn build_feed(site: &Site, info: FeedInfo, posts: &[&BlogPost]) -> Result<Feed> {
+    let config = site.config();
+    let mut feed = Feed {
+        title: Text::from(info.title),
+        id: info.id,
+        authors: vec![Person {
+            name: config.author.name.clone(),
+            email: Some(config.author.email.clone()),
+            uri: Some(config.author.uri.clone()),
+        }],
+        generator: Some(Generator {
+            value: "atom_syndication".into(),
+            uri: Some("https://github.com/rust-syndication/atom".into()),
+            version: Some("0.12.4".into()),
+        }),
+        links: vec![
+            Link {
+                href: info.alternate_url,
+                rel: "alternate".into(),
+                mime_type: Some("text/html".into()),
+                ..Default::default()
+            },
+            Link {
+                href: info.self_url,
+                rel: "self".into(),
+                mime_type: Some("application/atom+xml".into()),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let mut entries: Vec<Entry> = posts
+        .par_iter()
+        .map(|post| -> Result<Entry> { post.get_atom_entry(site) })
+        .collect::<Result<_>>()?;
+
+    feed.set_entries(entries.clone());
+    entries.sort_by(|entry1, entry2| -> Ordering { entry2.updated().cmp(entry1.updated()) });
+    if let Some(entry) = entries.first() {
+        feed.set_updated(*entry.updated());
+    }
+    Ok(feed)
+}
+
+fn write_feed_file(feed: &Feed, feed_path: &str) -> Result<()> {
+    write_if_changed(feed_path, feed.to_string().as_bytes())
+}
+
+fn write_feed(site: &Site, target_directory: &str, cache: &mut Cache) -> Result<()> {
+    let config = site.config();
+    let output = format!("{}/{}", config.output.blog, config.output.feed);
+    if cache.is_fresh(&output, cache.posts_key()) {
+        return Ok(());
+    }
+    let info = FeedInfo {
+        title: config.title.clone(),
+        id: config.feed_id(),
+        alternate_url: config.base_url.clone(),
+        self_url: config.feed_url(),
+    };
+    let feed = build_feed(site, info, &site.posts().iter().collect::<Vec<_>>())?;
+    write_feed_file(&feed, &format!("{target_directory}/{output}"))
+}
+
+fn write_tag_feed(site: &Site, tag: &Tag, directory: &str) -> Result<()> {
+    let config = site.config();
+    let tag_url = config.tag_url(&tag.slug);
+    let info = FeedInfo {
+        title: format!("{}: {}", config.title, tag.name),
+        id: format!("{tag_url}/"),
+        alternate_url: format!("{tag_url}/"),
+        self_url: format!("{tag_url}/{}", config.output.feed),
+    };
+    let feed = build_feed(site, info, &tag.posts)?;
+    write_feed_file(&feed, &format!("{directory}/{}", config.output.feed))
+}
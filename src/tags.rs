@@ -0,0 +1,107 @@
+use minijinja::{context, Value};
+
+use crate::blog_post::BlogPost;
+
+/// A tag and the posts carrying it, newest first.
+pub struct Tag<'a> {
+    pub name: String,
+    pub slug: String,
+    pub posts: Vec<&'a BlogPost>,
+}
+
+/// URL path segment for a tag, series or new post, e.g. "Shaders in GDScript!" becomes
+/// "shaders-in-gdscript" and "C#" becomes "c". Letters and digits are lowercased, every run of
+/// other characters becomes a single `-`, so the result is empty for text without any.
+pub fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    let mut separated = false;
+    for character in text.chars() {
+        if character.is_alphanumeric() {
+            if separated && !slug.is_empty() {
+                slug.push('-');
+            }
+            separated = false;
+            slug.extend(character.to_lowercase());
+        } else {
+            separated = true;
+        }
+    }
+    slug
+}
+
+/// Whether two tag names are spellings of the same tag, only differing in case or spacing.
+pub fn same_tag(name: &str, other: &str) -> bool {
+    let normalized = |name: &str| {
+        name.split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase()
+    };
+    normalized(name) == normalized(other)
+}
+
+/// The tag as seen by templates.
+pub fn tag_context(name: &str) -> Value {
+    context! {
+        name => name,
+        slug => slug(name),
+    }
+}
+
+/// Groups posts by tag, sorted by tag name. Tags that only differ in case or spacing are
+/// merged, keeping the spelling of the newest post. Other tags can't share a slug, loading the
+/// site reports them.
+pub fn collect_tags(blog_posts: &[BlogPost]) -> Vec<Tag<'_>> {
+    let mut tags: Vec<Tag> = Vec::new();
+    for post in blog_posts {
+        for name in &post.tags {
+            let tag_slug = slug(name);
+            match tags.iter_mut().find(|tag| tag.slug == tag_slug) {
+                Some(tag) => {
+                    if !tag.posts.iter().any(|tagged| tagged.id == post.id) {
+                        tag.posts.push(post);
+                    }
+                }
+                None => tags.push(Tag {
+                    name: name.clone(),
+                    slug: tag_slug,
+                    posts: vec![post],
+                }),
+            }
+        }
+    }
+    tags.sort_by_key(|tag| tag.name.to_lowercase());
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slug_keeps_words_together() {
+        assert_eq!(slug("GDScript"), "gdscript");
+        assert_eq!(slug("iOS"), "ios");
+        assert_eq!(slug("3D"), "3d");
+        assert_eq!(
+            slug("Using GDScript in Godot 4"),
+            "using-gdscript-in-godot-4"
+        );
+    }
+
+    #[test]
+    fn slug_collapses_everything_else_into_dashes() {
+        assert_eq!(slug("Web/Dev"), "web-dev");
+        assert_eq!(slug("  Shaders -- in   Godot!  "), "shaders-in-godot");
+        assert_eq!(slug("C#"), "c");
+        assert_eq!(slug("Über"), "über");
+        assert_eq!(slug("???"), "");
+    }
+
+    #[test]
+    fn same_tag_ignores_case_and_spacing_only() {
+        assert!(same_tag("Game  Dev", "game dev"));
+        assert!(!same_tag("C#", "C++"));
+        assert!(!same_tag("web-dev", "Web/Dev"));
+    }
+}
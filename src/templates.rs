@@ -0,0 +1,159 @@
+use std::{fmt::Write, fs, io};
+
+use chrono::DateTime;
+use minijinja::{escape_formatter, AutoEscape, Environment, Error, ErrorKind, Value};
+use regex::Regex;
+
+use crate::{config::SiteConfig, error::Result, mdast_html::escape, renderer::Section};
+
+/// Layouts that ship with scrigen, used unless the site provides its own.
+pub(crate) const DEFAULT_TEMPLATES: [(&str, &str); 11] = [
+    ("post.html", include_str!("../templates/post.html")),
+    ("page.html", include_str!("../templates/page.html")),
+    ("landing.html", include_str!("../templates/landing.html")),
+    ("entry.html", include_str!("../templates/entry.html")),
+    (
+        "pagination.html",
+        include_str!("../templates/pagination.html"),
+    ),
+    ("nav.html", include_str!("../templates/nav.html")),
+    ("tag.html", include_str!("../templates/tag.html")),
+    ("tags.html", include_str!("../templates/tags.html")),
+    ("archive.html", include_str!("../templates/archive.html")),
+    (
+        "post_navigation.html",
+        include_str!("../templates/post_navigation.html"),
+    ),
+    (
+        "tag_chips.html",
+        include_str!("../templates/tag_chips.html"),
+    ),
+];
+
+/// Page layouts, looked up by name in the site's templates directory, then its fragments
+/// directory and finally among scrigen's defaults.
+pub struct Templates {
+    environment: Environment<'static>,
+    include_regex: Regex,
+}
+
+impl Templates {
+    pub fn load(config: &SiteConfig) -> Self {
+        let directories = [config.templates_directory(), config.fragments_directory()];
+        let mut environment = Environment::new();
+        environment.set_loader(move |name| {
+            for directory in &directories {
+                match fs::read_to_string(format!("{directory}/{name}")) {
+                    Ok(source) => return Ok(Some(source)),
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                    Err(error) => {
+                        return Err(Error::new(
+                            ErrorKind::InvalidOperation,
+                            format!("could not read {directory}/{name}"),
+                        )
+                        .with_source(error))
+                    }
+                }
+            }
+            Ok(DEFAULT_TEMPLATES
+                .iter()
+                .find(|(default_name, _)| *default_name == name)
+                .map(|(_, source)| source.to_string()))
+        });
+        // Like the default formatter, but without escaping slashes in URLs.
+        environment.set_formatter(|output, state, value| match value.as_str() {
+            Some(string) if state.auto_escape() == AutoEscape::Html && !value.is_safe() => {
+                Ok(output.write_str(&escape(string).replace('\'', "&#39;"))?)
+            }
+            _ => escape_formatter(output, state, value),
+        });
+        environment.add_filter("date", date);
+        Self {
+            environment,
+            include_regex: Regex::new(
+                r#"\{%-?\s*(?:include|extends|import|from)\s+["']([^"']+)["']"#,
+            )
+            .unwrap(),
+        }
+    }
+
+    pub fn render(&self, name: &str, context: Value) -> Result<String> {
+        Ok(self.environment.get_template(name)?.render(context)?)
+    }
+
+    /// Whether rendering the template `name` uses `dependency`, directly or through includes.
+    /// Includes with a name computed at render time aren't followed.
+    pub fn depends_on(&self, name: &str, dependency: &str) -> Result<bool> {
+        let mut visited: Vec<String> = Vec::new();
+        let mut pending = vec![name.to_string()];
+        while let Some(name) = pending.pop() {
+            if name == dependency {
+                return Ok(true);
+            }
+            if visited.contains(&name) {
+                continue;
+            }
+            let source = self.environment.get_template(&name)?.source().to_string();
+            pending.extend(
+                self.include_regex
+                    .captures_iter(&source)
+                    .map(|captures| captures[1].to_string()),
+            );
+            visited.push(name);
+        }
+        Ok(false)
+    }
+}
+
+/// Formats an RFC 3339 timestamp, by default as e.g. "October 5, 2024".
+fn date(value: &str, format: Option<&str>) -> std::result::Result<String, Error> {
+    let date = DateTime::parse_from_rfc3339(value).map_err(|error| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid date '{value}'"),
+        )
+        .with_source(error)
+    })?;
+    let format = format.unwrap_or("%B %e, %Y");
+    let mut formatted = String::new();
+    // Invalid formats only fail once the date is written.
+    write!(formatted, "{}", date.format(format)).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("invalid date format '{format}'"),
+        )
+    })?;
+    Ok(formatted)
+}
+
+/// Section titles are rendered Markdown and must not be escaped again.
+pub fn sections_value(sections: &[Section]) -> Value {
+    sections
+        .iter()
+        .map(|section| {
+            minijinja::context! {
+                id => section.id,
+                title => Value::from_safe_string(section.title.clone()),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_formats_timestamps() {
+        assert_eq!(
+            date("2024-10-05T18:30:00+02:00", Some("%Y-%m-%d %H:%M")).unwrap(),
+            "2024-10-05 18:30"
+        );
+    }
+
+    #[test]
+    fn date_rejects_invalid_formats_and_dates() {
+        assert!(date("2024-10-05T18:30:00+02:00", Some("%Q")).is_err());
+        assert!(date("yesterday", None).is_err());
+    }
+}
@@ -0,0 +1,424 @@
+//! Watches a site's sources and works out which parts of the site a change affects.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::{
+    config::SiteConfig,
+    error::{Result, ScrigenError},
+    site::{Site, Step},
+    static_page::StaticPage,
+};
+
+/// Changes arriving within this time of each other are handled together.
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Calls `on_change` with the changed paths whenever the content directories, the templates
+/// or `site.ron` change, until the process is stopped.
+pub fn watch(source_directory: &str, mut on_change: impl FnMut(&[PathBuf])) -> Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher =
+        notify::recommended_watcher(sender).map_err(|source| ScrigenError::Watch {
+            path: source_directory.to_string(),
+            source,
+        })?;
+    for (path, mode) in watched_paths(source_directory)? {
+        watcher
+            .watch(&path, mode)
+            .map_err(|source| ScrigenError::Watch {
+                path: path.to_string_lossy().to_string(),
+                source,
+            })?;
+    }
+    while let Ok(event) = receiver.recv() {
+        let mut paths = Vec::new();
+        collect_changes(event, &mut paths);
+        while let Ok(event) = receiver.recv_timeout(DEBOUNCE) {
+            collect_changes(event, &mut paths);
+        }
+        if !paths.is_empty() {
+            on_change(&paths);
+        }
+    }
+    Ok(())
+}
+
+/// The content directories, the templates and `site.ron`. Editors often replace files instead
+/// of writing to them, so `site.ron` is watched through the source directory.
+fn watched_paths(source_directory: &str) -> Result<Vec<(PathBuf, RecursiveMode)>> {
+    let config = SiteConfig::load(source_directory)?;
+    let mut paths = Vec::new();
+    for (directory, mode) in [
+        (source_directory.to_string(), RecursiveMode::NonRecursive),
+        (config.entries_directory(), RecursiveMode::Recursive),
+        (config.pages_directory(), RecursiveMode::Recursive),
+        (config.fragments_directory(), RecursiveMode::Recursive),
+        (config.templates_directory(), RecursiveMode::Recursive),
+    ] {
+        // Watching canonical paths makes the changed paths comparable to them.
+        if let Ok(directory) = Path::new(&directory).canonicalize() {
+            paths.push((directory, mode));
+        }
+    }
+    Ok(paths)
+}
+
+/// Building reads every source file, which must not count as a change.
+fn collect_changes(event: notify::Result<Event>, paths: &mut Vec<PathBuf>) {
+    match event {
+        Ok(event) if matches!(event.kind, EventKind::Access(_)) => {}
+        Ok(event) => {
+            for path in event.paths {
+                if !paths.contains(&path) {
+                    paths.push(path);
+                }
+            }
+        }
+        Err(error) => eprintln!("warning: {error}"),
+    }
+}
+
+/// The parts of a site that need to be written again after some of its sources changed.
+#[derive(Default, Debug)]
+pub struct Plan {
+    /// Complete build steps to run.
+    pub steps: Vec<Step>,
+    /// Ids of single posts to write, besides the ones written by [`Step::Posts`].
+    pub posts: Vec<String>,
+    /// Ids of single pages to write, besides the ones written by [`Step::Pages`].
+    pub pages: Vec<String>,
+}
+
+impl Plan {
+    pub fn everything() -> Self {
+        Self {
+            steps: Step::ALL.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    /// Works out what to rebuild after `paths` changed, with `site` loaded after the change.
+    ///
+    /// Editing a post's `content.md` only affects the post and the feeds, its `meta.ron` also
+    /// affects the lists of posts, the posts linking to it and menu items labelled with its
+    /// tags. Pages appear in the navigation of every page, so changing which pages exist, how
+    /// they are nested or what they are called rebuilds everything. Templates and fragments
+    /// rebuild the steps whose templates include them.
+    pub fn for_changes(site: &Site, paths: &[PathBuf]) -> Result<Self> {
+        let config = site.config();
+        let canonical = |directory: String| Path::new(&directory).canonicalize().ok();
+        let source_directory = canonical(config.source_directory.clone());
+        let entries_directory = canonical(config.entries_directory());
+        let pages_directory = canonical(config.pages_directory());
+        let template_directories = [
+            canonical(config.templates_directory()),
+            canonical(config.fragments_directory()),
+        ];
+
+        let mut plan = Self::default();
+        for path in paths {
+            if let Some(file) = relative(path, &entries_directory) {
+                let (directory, file) = split_first(&file);
+                match file.as_str() {
+                    // Other posts link to the post and menu items can be labelled with the
+                    // spelling of its tags, the cache skips the outputs that didn't change.
+                    "" | "meta.ron" => plan.add_steps(&Step::ALL),
+                    "content.md" => {
+                        plan.add_steps(&[Step::Feed]);
+                        // Tag feeds contain the complete post.
+                        if config.features.feed {
+                            plan.add_steps(&[Step::Tags]);
+                        }
+                        plan.add_post(site, &directory);
+                    }
+                    _ => plan.add_post(site, &directory),
+                }
+            } else if relative(path, &pages_directory).is_some() {
+                // The innermost page containing the path, pages may be nested.
+                let page = site
+                    .pages()
+                    .iter()
+                    .filter_map(|page| {
+                        Some((page, relative(path, &canonical(page.source_path.clone()))?))
+                    })
+                    .max_by_key(|(page, _)| page.depth());
+                match page {
+                    Some((page, file))
+                        if !file.is_empty()
+                            && file != "meta.ron"
+                            && !file.ends_with("/meta.ron") =>
+                    {
+                        plan.add_page(page)
+                    }
+                    _ => plan.add_steps(&Step::ALL),
+                }
+            } else if let Some(name) = template_directories
+                .iter()
+                .find_map(|directory| relative(path, directory))
+            {
+                for step in Step::ALL {
+                    for template in step.templates(config) {
+                        if site.templates().depends_on(template, &name)? {
+                            plan.add_steps(&[step]);
+                        }
+                    }
+                }
+            } else if source_directory
+                .as_ref()
+                .is_some_and(|directory| *path == directory.join("site.ron"))
+            {
+                plan.add_steps(&Step::ALL);
+            }
+        }
+        Ok(plan)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty() && self.posts.is_empty() && self.pages.is_empty()
+    }
+
+    /// Writes everything in the plan into `target_directory`.
+    pub fn run(&self, site: &Site, target_directory: &str) -> Result<()> {
+        for step in Step::ALL {
+            if self.steps.contains(&step) {
+                site.build_step(step, target_directory)?;
+            }
+        }
+        if !self.steps.contains(&Step::Posts) {
+            for post in site.posts() {
+                if self.posts.contains(&post.id) {
+                    site.build_post(post, target_directory)?;
+                }
+            }
+        }
+        if !self.steps.contains(&Step::Pages) {
+            for page in site.pages() {
+                if self.pages.contains(&page.id) {
+                    site.build_page(page, target_directory)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_steps(&mut self, steps: &[Step]) {
+        for step in steps {
+            if !self.steps.contains(step) {
+                self.steps.push(*step);
+            }
+        }
+    }
+
+    fn add_post(&mut self, site: &Site, directory: &str) {
+        // Posts are identified by their directory name, drafts and scheduled posts that
+        // aren't part of the build are skipped.
+        if site.posts().iter().any(|post| post.id == directory)
+            && !self.posts.iter().any(|id| id == directory)
+        {
+            self.posts.push(directory.to_string());
+        }
+    }
+
+    fn add_page(&mut self, page: &StaticPage) {
+        if !self.pages.contains(&page.id) {
+            self.pages.push(page.id.clone());
+        }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut parts: Vec<String> = self.steps.iter().map(ToString::to_string).collect();
+        if !self.steps.contains(&Step::Posts) {
+            parts.extend(self.posts.iter().map(|id| format!("post {id}")));
+        }
+        if !self.steps.contains(&Step::Pages) {
+            parts.extend(self.pages.iter().map(|id| format!("page {id}")));
+        }
+        f.write_str(&parts.join(", "))
+    }
+}
+
+/// `path` relative to `directory` with `/` as separator, if it is inside of it.
+fn relative(path: &Path, directory: &Option<PathBuf>) -> Option<String> {
+    let relative = path.strip_prefix(directory.as_ref()?).ok()?;
+    Some(
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+    )
+}
+
+/// Splits a relative path into its first component and the rest.
+fn split_first(path: &str) -> (String, String) {
+    match path.split_once('/') {
+        Some((first, rest)) => (first.to_string(), rest.to_string()),
+        None => (path.to_string(), String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    /// A small site in a temporary directory, removed again when dropped.
+    struct Fixture {
+        directory: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(name: &str, features: &str) -> Self {
+            let directory = std::env::temp_dir()
+                .join(format!("scrigen-watch-{name}-{}", std::process::id()))
+                .canonicalize_or_self();
+            let _ = fs::remove_dir_all(&directory);
+            let fixture = Self { directory };
+            fixture.write(
+                "site.ron",
+                &format!(
+                    r#"(
+    title: "Test",
+    base_url: "https://example.org",
+    author: (name: "Author", email: "author@example.org", uri: "https://example.org"),
+    features: ({features}),
+)"#
+                ),
+            );
+            fixture.write(
+                "entries/post/meta.ron",
+                r#"(
+    title: "Post",
+    summary: "",
+    image: "",
+    image_alt: "",
+    published: "2024-01-01",
+    updated: "2024-01-02",
+    tags: ["Godot"],
+)"#,
+            );
+            fixture.write("entries/post/content.md", "Text");
+            fixture.write("entries/post/image.png", "");
+            fixture.write(
+                "pages/1_about/meta.ron",
+                r#"(name: "About", title: "About", summary: "")"#,
+            );
+            fixture.write("pages/1_about/content.md", "Text");
+            fixture.write("pages/1_about/image.png", "");
+            for fragment in [
+                "landing_header.html",
+                "landing_footer.html",
+                "page_header.html",
+                "post_header.html",
+                "post_footer.html",
+            ] {
+                fixture.write(&format!("fragments/{fragment}"), "");
+            }
+            fixture
+        }
+
+        fn write(&self, path: &str, contents: &str) {
+            let path = self.directory.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        fn plan(&self, path: &str) -> Plan {
+            let site = Site::load(&self.directory.to_string_lossy()).unwrap();
+            Plan::for_changes(&site, &[self.directory.join(path)]).unwrap()
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.directory);
+        }
+    }
+
+    trait CanonicalizeOrSelf {
+        fn canonicalize_or_self(self) -> Self;
+    }
+
+    impl CanonicalizeOrSelf for PathBuf {
+        /// Changed paths are compared to canonical ones, but the fixture doesn't exist yet.
+        fn canonicalize_or_self(self) -> Self {
+            fs::create_dir_all(&self).unwrap();
+            self.canonicalize().unwrap()
+        }
+    }
+
+    fn assert_steps(plan: &Plan, steps: &[Step]) {
+        assert_eq!(plan.steps.len(), steps.len(), "{plan:?}");
+        for step in steps {
+            assert!(plan.steps.contains(step), "{plan:?} lacks {step}");
+        }
+    }
+
+    #[test]
+    fn post_content_rebuilds_the_post_and_the_feeds() {
+        let fixture = Fixture::new("content", "");
+        let plan = fixture.plan("entries/post/content.md");
+        assert_steps(&plan, &[Step::Feed, Step::Tags]);
+        assert_eq!(plan.posts, ["post"]);
+    }
+
+    #[test]
+    fn post_content_without_feeds_leaves_the_tags_alone() {
+        let fixture = Fixture::new("content-no-feed", "feed: false");
+        let plan = fixture.plan("entries/post/content.md");
+        assert_steps(&plan, &[Step::Feed]);
+        assert_eq!(plan.posts, ["post"]);
+    }
+
+    #[test]
+    fn post_meta_rebuilds_everything() {
+        let fixture = Fixture::new("meta", "");
+        assert_steps(&fixture.plan("entries/post/meta.ron"), &Step::ALL);
+    }
+
+    #[test]
+    fn post_asset_rebuilds_the_post() {
+        let fixture = Fixture::new("asset", "");
+        let plan = fixture.plan("entries/post/image.png");
+        assert_steps(&plan, &[]);
+        assert_eq!(plan.posts, ["post"]);
+    }
+
+    #[test]
+    fn page_files_rebuild_the_page_and_page_meta_everything() {
+        let fixture = Fixture::new("page", "");
+        let plan = fixture.plan("pages/1_about/image.png");
+        assert_steps(&plan, &[]);
+        assert_eq!(plan.pages, ["about"]);
+        assert_steps(&fixture.plan("pages/1_about/meta.ron"), &Step::ALL);
+    }
+
+    #[test]
+    fn post_fragment_rebuilds_posts_and_feeds() {
+        let fixture = Fixture::new("fragment", "");
+        let plan = fixture.plan("fragments/post_header.html");
+        assert_steps(&plan, &[Step::Posts, Step::Feed, Step::Tags]);
+    }
+
+    #[test]
+    fn post_fragment_without_feeds_leaves_the_tags_alone() {
+        let fixture = Fixture::new("fragment-no-feed", "feed: false");
+        let plan = fixture.plan("fragments/post_header.html");
+        assert_steps(&plan, &[Step::Posts, Step::Feed]);
+    }
+
+    #[test]
+    fn site_config_rebuilds_everything() {
+        let fixture = Fixture::new("config", "");
+        assert_steps(&fixture.plan("site.ron"), &Step::ALL);
+    }
+}